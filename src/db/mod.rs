@@ -1,7 +1,10 @@
+use crate::hash::crop::Region;
 use crate::hash::{ImgHash, ImgHashKind};
+use crate::quality::Metadata;
 use directories::ProjectDirs;
 use log::{debug, trace};
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, params_from_iter, Connection, Result};
+use std::collections::{HashMap, HashSet};
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
 use std::vec::Vec;
@@ -9,24 +12,78 @@ use std::vec::Vec;
 // TODO might need to mv all const to common location
 const IDUP_DIR_NAME: &str = "idup";
 const IDUP_DB_NAME: &str = "idup.db3";
+const IDUP_INDEX_NAME: &str = "idup.mih";
 
 #[derive(Debug)]
 pub struct ImgData {
+    pub images_id: i64,
     pub path: PathBuf,
     pub sha256: String,
     pub phash: String,
+    pub color: String,
+    pub has_alpha: bool,
 }
 
-pub fn exact_match(path: &PathBuf) -> Result<Vec<ImgData>> {
+// a file moved out of the way by dedupe, undo moves it back from dst to src
+#[derive(Debug)]
+pub struct Action {
+    pub action_id: i64,
+    pub batch: i64,
+    pub src: PathBuf,
+    pub dst: PathBuf,
+}
+
+// a sha256 hash shared by more than one image
+#[derive(Debug)]
+pub struct HashMatch {
+    pub path: PathBuf,
+    pub kind: String,
+    pub hash: String,
+}
+
+// every sha256 hash (of any orientation) that is shared between different images
+pub fn exact_matches() -> Result<Vec<HashMatch>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        "
+        SELECT i.path, a.kind, a.hash
+        FROM images i
+        JOIN hashes a
+          ON i.images_id = a.images_id
+        JOIN (
+          SELECT hash
+          FROM hashes
+          WHERE kind LIKE 'sha256%'
+          GROUP BY hash
+          HAVING count(DISTINCT images_id) > 1
+        ) b
+          ON a.hash = b.hash
+        WHERE a.kind LIKE 'sha256%'
+        ORDER BY a.hash, i.path
+        ;",
+    )?;
+    let iter = stmt.query_map([], |row| {
+        let s: String = row.get(0)?;
+        Ok(HashMatch {
+            path: Path::new(&s).to_path_buf(),
+            kind: row.get(1)?,
+            hash: row.get(2)?,
+        })
+    })?;
+    iter.collect()
+}
+
+// sha256 hashes shared between an image at path (or any image under it if it's a dir) & other images
+pub fn exact_match(path: &Path) -> Result<Vec<HashMatch>> {
     debug!("exact_match on path: {:?}", path);
     // TODO I could use a struct & pass that in to enforce the absolute path
     // SAFETY: all paths passed to the db need to be absolute
     let path = path.canonicalize().unwrap();
     let conn = open_db()?;
-    // TODO should we show the given path in the output?
-    let mut stmt = conn.prepare(
+    let (filter, args) = path_filter(&path);
+    let mut stmt = conn.prepare(&format!(
         "
-        SELECT DISTINCT i_dup.path
+        SELECT DISTINCT i_dup.path, h_dup.kind, h_dup.hash
         FROM images i
         JOIN hashes h
           ON i.images_id = h.images_id
@@ -34,53 +91,151 @@ pub fn exact_match(path: &PathBuf) -> Result<Vec<ImgData>> {
           ON h.hash = h_dup.hash
         JOIN images i_dup
           ON h_dup.images_id = i_dup.images_id
-        WHERE i.path = (?1)
+        WHERE {}
           AND h.kind LIKE 'sha256%'
           AND h_dup.kind LIKE 'sha256%'
+        ORDER BY h_dup.hash, i_dup.path
         ;",
-    )?;
-    let iter = stmt.query_map([path.to_str()], |row| {
+        filter
+    ))?;
+    let iter = stmt.query_map(params_from_iter(args), |row| {
         let path: String = row.get(0)?;
-        // TODO ImgData might not be the right struct here
-        Ok(ImgData {
+        Ok(HashMatch {
+            path: Path::new(&path).to_path_buf(),
+            kind: row.get(1)?,
+            hash: row.get(2)?,
+        })
+    })?;
+    iter.collect()
+}
+
+// every sha256 hash (file & all orientations) of the images under dir
+pub fn sha256_under(dir: &Path) -> Result<Vec<HashMatch>> {
+    // SAFETY: all paths passed to the db need to be absolute
+    let dir = dir.canonicalize().unwrap();
+    let conn = open_db()?;
+    let (filter, args) = path_filter(&dir);
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT i.path, h.kind, h.hash
+        FROM images i
+        JOIN hashes h
+          ON i.images_id = h.images_id
+        WHERE {}
+          AND h.kind LIKE 'sha256%'
+        ORDER BY i.path
+        ;",
+        filter
+    ))?;
+    let iter = stmt.query_map(params_from_iter(args), |row| {
+        let path: String = row.get(0)?;
+        Ok(HashMatch {
             path: Path::new(&path).to_path_buf(),
-            sha256: "".to_string(),
-            phash: "".to_string(),
+            kind: row.get(1)?,
+            hash: row.get(2)?,
+        })
+    })?;
+    iter.collect()
+}
+
+// sql condition on images i & its args matching the path itself, or everything under it if it's a dir
+fn path_filter(path: &Path) -> (&'static str, Vec<String>) {
+    if path.is_dir() {
+        let prefix = dir_prefix(path);
+        // LIKE is case insensitive for ascii, so the substr makes sure the prefix really matches
+        (
+            "i.path LIKE ?1 || '%' ESCAPE '\\' AND substr(i.path, 1, length(?2)) = ?2",
+            vec![escape_like(&prefix), prefix],
+        )
+    } else {
+        ("i.path = ?1", vec![path.to_string_lossy().to_string()])
+    }
+}
+
+// the dir as a string that always ends w/ a separator, so /a/b doesn't match /a/bc
+fn dir_prefix(dir: &Path) -> String {
+    let mut prefix = dir.to_string_lossy().to_string();
+    if !prefix.ends_with(std::path::MAIN_SEPARATOR) {
+        prefix.push(std::path::MAIN_SEPARATOR);
+    }
+    prefix
+}
+
+// escapes the LIKE wildcards (w/ '\' as the escape char) so they match literally
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// images w/ a sha256 hash (file or any orientation of the image data) equal to the given one
+pub fn lookup_sha256(hash: &str) -> Result<Vec<HashMatch>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        "
+        SELECT i.path, h.kind, h.hash
+        FROM images i
+        JOIN hashes h
+          ON i.images_id = h.images_id
+        WHERE h.hash = ?1
+          AND h.kind LIKE 'sha256%'
+        ORDER BY i.path
+        ;",
+    )?;
+    let iter = stmt.query_map([hash], |row| {
+        let s: String = row.get(0)?;
+        Ok(HashMatch {
+            path: Path::new(&s).to_path_buf(),
+            kind: row.get(1)?,
+            hash: row.get(2)?,
         })
     })?;
     iter.collect()
 }
 
-pub fn exact_matches() -> Result<Vec<ImgData>> {
+// all images that have a phash, along w/ their imgdata sha256 & color signature (empty if never computed)
+pub fn fuzzy_candidates() -> Result<Vec<ImgData>> {
     let conn = open_db()?;
     let mut stmt = conn.prepare(
         "
-        SELECT DISTINCT i.path, b.cnt
+        SELECT i.path, p.hash, c.hash, s.hash, a.has_alpha, i.images_id
         FROM images i
-        JOIN hashes a
+        JOIN hashes p
+          ON i.images_id = p.images_id
+         AND p.kind = 'phash'
+        LEFT JOIN hashes s
+          ON i.images_id = s.images_id
+         AND s.kind = 'sha256 imgdata'
+        LEFT JOIN alpha a
           ON i.images_id = a.images_id
-        JOIN (
-          SELECT hash, count(*) as cnt
-          FROM hashes
-          WHERE kind like 'sha256%'
-          GROUP BY hash
-          HAVING count(*) > 1
-        ) b
-          ON a.hash = b.hash
-        ORDER BY b.cnt DESC
+        LEFT JOIN hashes c
+          ON i.images_id = c.images_id
+         AND c.kind = 'color'
+        ORDER BY i.path
         ;",
     )?;
     let iter = stmt.query_map([], |row| {
-        let s: String = row.get(0)?;
+        let path: String = row.get(0)?;
+        let color: Option<String> = row.get(2)?;
+        let sha256: Option<String> = row.get(3)?;
         Ok(ImgData {
-            path: Path::new(&s).to_path_buf(),
-            sha256: "".to_string(),
-            phash: "".to_string(), // TODO this isn't the phash
+            images_id: row.get(5)?,
+            path: Path::new(&path).to_path_buf(),
+            sha256: sha256.unwrap_or_default(),
+            phash: row.get(1)?,
+            color: color.unwrap_or_default(),
+            has_alpha: row.get::<_, Option<bool>>(4)?.unwrap_or(false),
         })
     })?;
     iter.collect()
 }
 
+#[allow(clippy::single_match)]
 pub fn save(img: &ImgHash) -> Result<(), rusqlite::Error> {
     let conn = open_db()?;
     // check if img already in db
@@ -103,13 +258,307 @@ pub fn save(img: &ImgHash) -> Result<(), rusqlite::Error> {
     // now save partial_hashes
     match img.kind {
         ImgHashKind::Phash => {
-            save_partial_phash(&img, &conn)?;
+            save_partial_phash(img, &conn)?;
         }
         _ => {}
     }
+    bump_generation(&conn)?;
+    Ok(())
+}
+
+// forgets an image & everything computed from it, e.g. after the file was deleted
+pub fn remove(path: &Path) -> Result<(), rusqlite::Error> {
+    let mut conn = open_db()?;
+    let tx = conn.transaction()?;
+    remove_in(&tx, path)?;
+    bump_generation(&tx)?;
+    tx.commit()
+}
+
+// records the files dedupe or a review quarantined, forgets them & saves the pairs that aren't
+// duplicates, all or nothing
+pub fn save_review(batch: i64, moves: &[(PathBuf, PathBuf)], not_duplicates: &[(&str, &str)]) -> Result<()> {
+    let mut conn = open_db()?;
+    let tx = conn.transaction()?;
+    for (src, dst) in moves {
+        tx.execute(
+            "INSERT INTO actions (batch, src, dst, undone) values (?1, ?2, ?3, 0)",
+            params![batch, src.to_str(), dst.to_str()],
+        )?;
+        remove_in(&tx, src)?;
+    }
+    insert_not_duplicates(&tx, not_duplicates)?;
+    bump_generation(&tx)?;
+    tx.commit()
+}
+
+fn remove_in(conn: &Connection, path: &Path) -> Result<()> {
+    for table in [
+        "hashes",
+        "partial_hashes",
+        "crop_hashes",
+        "frames",
+        "alpha",
+        "metadata",
+        "images",
+    ] {
+        conn.execute(
+            &format!(
+                "DELETE FROM {} WHERE images_id = (SELECT images_id FROM images WHERE path = ?1)",
+                table
+            ),
+            params![path.to_str()],
+        )?;
+    }
+    Ok(())
+}
+
+// every dedupe run gets its own batch so it can be undone as a whole
+pub fn next_batch() -> Result<i64> {
+    let conn = open_db()?;
+    conn.query_row("SELECT coalesce(max(batch), 0) + 1 FROM actions", [], |row| row.get(0))
+}
+
+// the action w/ the id, or every action of the latest batch that still has something to undo
+pub fn pending_actions(action_id: Option<i64>) -> Result<Vec<Action>> {
+    let conn = open_db()?;
+    let filter = match action_id {
+        Some(_) => "action_id = ?1",
+        None => "batch = (SELECT max(batch) FROM actions WHERE undone = 0)",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT action_id, batch, src, dst
+        FROM actions
+        WHERE {}
+          AND undone = 0
+        ORDER BY action_id
+        ;",
+        filter
+    ))?;
+    let iter = stmt.query_map(params_from_iter(action_id), |row| {
+        let src: String = row.get(2)?;
+        let dst: String = row.get(3)?;
+        Ok(Action {
+            action_id: row.get(0)?,
+            batch: row.get(1)?,
+            src: Path::new(&src).to_path_buf(),
+            dst: Path::new(&dst).to_path_buf(),
+        })
+    })?;
+    iter.collect()
+}
+
+pub fn mark_undone(action_id: i64) -> Result<()> {
+    let conn = open_db()?;
+    conn.execute("UPDATE actions SET undone = 1 WHERE action_id = ?1", params![action_id])?;
+    Ok(())
+}
+
+// records that the images w/ these imgdata hashes aren't duplicates of each other
+pub fn save_not_duplicates(pairs: &[(&str, &str)]) -> Result<()> {
+    let mut conn = open_db()?;
+    let tx = conn.transaction()?;
+    insert_not_duplicates(&tx, pairs)?;
+    tx.commit()
+}
+
+fn insert_not_duplicates(conn: &Connection, pairs: &[(&str, &str)]) -> Result<()> {
+    for (a, b) in pairs {
+        let (a, b) = pair_key(a, b);
+        conn.execute(
+            "INSERT OR REPLACE INTO pair_decisions (hash_a, hash_b, decision) values (?1, ?2, 'not_duplicate')",
+            params![a, b],
+        )?;
+    }
+    Ok(())
+}
+
+// imgdata hash pairs that aren't duplicates, as ordered by pair_key
+pub fn not_duplicates() -> Result<HashSet<(String, String)>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare("SELECT hash_a, hash_b FROM pair_decisions WHERE decision = 'not_duplicate';")?;
+    let iter = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    iter.collect()
+}
+
+// the same pair in either order gets the same key
+pub fn pair_key(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+// (random id of the db, generation) the generation increases every time the hashes change, used to
+// tell if caches built from the db are stale. the id tells apart a db that was deleted & recreated
+pub fn generation() -> Result<(u64, u64)> {
+    let conn = open_db()?;
+    let id: i64 = conn.query_row("SELECT value FROM meta WHERE key = 'id'", [], |row| row.get(0))?;
+    let generation = conn.query_row("SELECT value FROM meta WHERE key = 'generation'", [], |row| row.get(0))?;
+    Ok((id as u64, generation))
+}
+
+fn bump_generation(conn: &Connection) -> Result<()> {
+    conn.execute("UPDATE meta SET value = value + 1 WHERE key = 'generation'", [])?;
+    Ok(())
+}
+
+// where dedupe moves files to when quarantining them, next to the db
+pub fn quarantine_dir() -> PathBuf {
+    setup_dir().with_file_name("quarantine")
+}
+
+// where the fuzzy search index is cached, next to the db
+pub fn index_path() -> PathBuf {
+    setup_dir().with_file_name(IDUP_INDEX_NAME)
+}
+
+// records whether the image had meaningful transparency before it was flattened for hashing
+pub fn save_alpha(path: &Path, has_alpha: bool) -> Result<(), rusqlite::Error> {
+    let conn = open_db()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO alpha (images_id, has_alpha)
+           values ((SELECT images_id FROM images WHERE path = ?1), ?2)",
+        params![path.to_str(), has_alpha],
+    )?;
+    Ok(())
+}
+
+pub fn save_metadata(path: &Path, meta: &Metadata) -> Result<(), rusqlite::Error> {
+    let conn = open_db()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO metadata (images_id, width, height, bit_depth, file_size, format, lossless, jpeg_quality)
+           values ((SELECT images_id FROM images WHERE path = ?1), ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            path.to_str(),
+            meta.width,
+            meta.height,
+            meta.bit_depth,
+            meta.file_size,
+            meta.format,
+            meta.lossless,
+            meta.jpeg_quality
+        ],
+    )?;
     Ok(())
 }
 
+// metadata of every image scanned since it was added to the db
+pub fn metadata() -> Result<HashMap<PathBuf, Metadata>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        "
+        SELECT i.path, m.width, m.height, m.bit_depth, m.file_size, m.format, m.lossless, m.jpeg_quality
+        FROM images i
+        JOIN metadata m
+          ON i.images_id = m.images_id
+        ;",
+    )?;
+    let iter = stmt.query_map([], |row| {
+        let path: String = row.get(0)?;
+        let meta = Metadata {
+            width: row.get(1)?,
+            height: row.get(2)?,
+            bit_depth: row.get(3)?,
+            file_size: row.get(4)?,
+            format: row.get(5)?,
+            lossless: row.get(6)?,
+            jpeg_quality: row.get(7)?,
+        };
+        Ok((Path::new(&path).to_path_buf(), meta))
+    })?;
+    iter.collect()
+}
+
+// replaces the per frame phashes of an animated image, an empty list clears them
+pub fn save_frames(path: &Path, frames: &[u64]) -> Result<(), rusqlite::Error> {
+    let mut conn = open_db()?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM frames WHERE images_id = (SELECT images_id FROM images WHERE path = ?1)",
+        params![path.to_str()],
+    )?;
+    for (i, hash) in frames.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO frames (images_id, sequence, hash)
+               values ((SELECT images_id FROM images WHERE path = ?1), ?2, ?3)",
+            params![path.to_str(), i, hash.to_string()],
+        )?;
+    }
+    tx.commit()
+}
+
+// every animated image w/ its frame phashes in order
+pub fn animated() -> Result<Vec<(PathBuf, Vec<u64>)>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        "
+        SELECT i.path, f.hash
+        FROM images i
+        JOIN frames f
+          ON i.images_id = f.images_id
+        ORDER BY i.path, f.sequence
+        ;",
+    )?;
+    let mut results: Vec<(PathBuf, Vec<u64>)> = Vec::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let path = Path::new(&row.get::<_, String>(0)?).to_path_buf();
+        let hash = row.get::<_, String>(1)?.parse().unwrap_or_default();
+        match results.last_mut() {
+            Some((last, frames)) if *last == path => frames.push(hash),
+            _ => results.push((path, vec![hash])),
+        }
+    }
+    Ok(results)
+}
+
+// replaces the crop region hashes of an image, the image needs to already be saved
+pub fn save_crop_regions(path: &Path, regions: &[Region]) -> Result<(), rusqlite::Error> {
+    let mut conn = open_db()?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM crop_hashes WHERE images_id = (SELECT images_id FROM images WHERE path = ?1)",
+        params![path.to_str()],
+    )?;
+    for r in regions {
+        tx.execute(
+            "INSERT INTO crop_hashes (images_id, x, y, w, h, hash)
+               values ((SELECT images_id FROM images WHERE path = ?1), ?2, ?3, ?4, ?5, ?6)",
+            params![path.to_str(), r.x, r.y, r.w, r.h, r.hash.to_string()],
+        )?;
+    }
+    tx.commit()
+}
+
+pub fn crop_regions() -> Result<Vec<(PathBuf, Region)>> {
+    let conn = open_db()?;
+    let mut stmt = conn.prepare(
+        "
+        SELECT i.path, c.x, c.y, c.w, c.h, c.hash
+        FROM images i
+        JOIN crop_hashes c
+          ON i.images_id = c.images_id
+        ORDER BY i.path
+        ;",
+    )?;
+    let iter = stmt.query_map([], |row| {
+        let path: String = row.get(0)?;
+        let hash: String = row.get(5)?;
+        let region = Region {
+            x: row.get(1)?,
+            y: row.get(2)?,
+            w: row.get(3)?,
+            h: row.get(4)?,
+            hash: hash.parse().unwrap_or_default(),
+        };
+        Ok((Path::new(&path).to_path_buf(), region))
+    })?;
+    iter.collect()
+}
+
 fn save_partial_phash(img: &ImgHash, conn: &Connection) -> Result<(), rusqlite::Error> {
     // TODO split up the hash into multiple non-overlapping segments
     let chunk_size = 4;
@@ -141,6 +590,7 @@ fn open_db() -> Result<Connection> {
     Ok(conn)
 }
 
+#[allow(clippy::expect_fun_call)]
 fn setup_dir() -> PathBuf {
     let proj_dirs = ProjectDirs::from("", "", IDUP_DIR_NAME).expect("Could not determine user data directory");
 
@@ -180,6 +630,72 @@ fn setup_db(conn: &Connection) -> Result<()> {
           FOREIGN KEY (images_id) REFERENCES images (images_id)
         );
 
+        -- phashes of overlapping sub-regions, used to find crops of an image
+        -- x, y, w, h are in percent of the image's dimensions
+        CREATE TABLE IF NOT EXISTS crop_hashes (
+          images_id INTEGER,
+          x INTEGER,
+          y INTEGER,
+          w INTEGER,
+          h INTEGER,
+          hash TEXT,
+          PRIMARY KEY (images_id, x, y, w, h),
+          FOREIGN KEY (images_id) REFERENCES images (images_id)
+        );
+
+        -- phashes of each frame of animated images, still images have no rows
+        CREATE TABLE IF NOT EXISTS frames (
+          images_id INTEGER,
+          sequence INTEGER,
+          hash TEXT,
+          PRIMARY KEY (images_id, sequence),
+          FOREIGN KEY (images_id) REFERENCES images (images_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS meta (
+          key TEXT PRIMARY KEY,
+          value INTEGER
+        );
+        INSERT OR IGNORE INTO meta (key, value) values ('generation', 0);
+        INSERT OR IGNORE INTO meta (key, value) values ('id', random());
+
+        CREATE TABLE IF NOT EXISTS alpha (
+          images_id INTEGER PRIMARY KEY,
+          has_alpha INTEGER,
+          FOREIGN KEY (images_id) REFERENCES images (images_id)
+        );
+
+        -- used to pick the best copy in a group of duplicates
+        CREATE TABLE IF NOT EXISTS metadata (
+          images_id INTEGER PRIMARY KEY,
+          width INTEGER,
+          height INTEGER,
+          bit_depth INTEGER,
+          file_size INTEGER,
+          format TEXT,
+          lossless INTEGER,
+          jpeg_quality INTEGER,
+          FOREIGN KEY (images_id) REFERENCES images (images_id)
+        );
+
+        -- pairs of images the user decided about, keyed on their imgdata sha256 so the
+        -- decision follows the pixels when files are moved. hash_a is the smaller one
+        CREATE TABLE IF NOT EXISTS pair_decisions (
+          hash_a TEXT,
+          hash_b TEXT,
+          decision TEXT,
+          PRIMARY KEY (hash_a, hash_b)
+        );
+
+        -- files moved into quarantine by dedupe, so they can be restored
+        CREATE TABLE IF NOT EXISTS actions (
+          action_id INTEGER PRIMARY KEY AUTOINCREMENT,
+          batch INTEGER,
+          src TEXT,
+          dst TEXT,
+          undone INTEGER
+        );
+
         COMMIT;",
     )?;
 
@@ -194,3 +710,27 @@ fn setup_db(conn: &Connection) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_like_wildcards() {
+        assert_eq!(escape_like("/photos/100%_done/"), "/photos/100\\%\\_done/");
+        assert_eq!(escape_like("C:\\pics"), "C:\\\\pics");
+        assert_eq!(escape_like("/plain/"), "/plain/");
+    }
+
+    #[test]
+    fn dir_prefix_has_one_trailing_separator() {
+        assert_eq!(dir_prefix(Path::new("/a/b")), "/a/b/");
+        assert_eq!(dir_prefix(Path::new("/")), "/");
+    }
+
+    #[test]
+    fn pair_key_ignores_order() {
+        assert_eq!(pair_key("b", "a"), pair_key("a", "b"));
+        assert_eq!(pair_key("b", "a"), ("a".to_string(), "b".to_string()));
+    }
+}
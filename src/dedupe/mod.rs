@@ -0,0 +1,153 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+// which member of a duplicate group is kept, the others get removed. the members are byte for byte
+// identical, so there's no size or resolution to pick by
+#[derive(Debug, Clone, PartialEq)]
+pub enum Keep {
+    Oldest,
+    Newest,
+    ShortestPath,
+    // the first member under the dir, falls back to the first member
+    PathPriority(PathBuf),
+}
+
+pub fn parse_keep(s: &str) -> Result<Keep, String> {
+    match s {
+        "oldest" => Ok(Keep::Oldest),
+        "newest" => Ok(Keep::Newest),
+        "shortest-path" => Ok(Keep::ShortestPath),
+        _ => match s.strip_prefix("path-priority=") {
+            // SAFETY: all paths in the db are absolute
+            Some(dir) => Ok(Keep::PathPriority(
+                Path::new(dir).canonicalize().map_err(|e| format!("{}: {}", dir, e))?,
+            )),
+            None => Err(format!(
+                "expected one of oldest, newest, shortest-path, path-priority=<dir> but got {}",
+                s
+            )),
+        },
+    }
+}
+
+// index of the member to keep, ties go to the first one
+pub fn keeper(paths: &[&Path], keep: &Keep) -> usize {
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let indexes = 0..paths.len();
+    let best = match keep {
+        // files whose mtime can't be read are never kept over ones that can
+        Keep::Oldest => indexes.min_by_key(|i| (modified(paths[*i]).is_none(), modified(paths[*i]), *i)),
+        Keep::Newest => indexes.min_by_key(|i| (Reverse(modified(paths[*i]).unwrap_or(SystemTime::UNIX_EPOCH)), *i)),
+        Keep::ShortestPath => indexes.min_by_key(|i| (paths[*i].as_os_str().len(), *i)),
+        Keep::PathPriority(dir) => indexes.min_by_key(|i| (!paths[*i].starts_with(dir), *i)),
+    };
+    best.unwrap_or(0)
+}
+
+// the members that can be removed w/o losing data. symlinks (e.g. left by link --mode symlink) & other
+// paths to a file already in the list are left out, removing the file they lead to would lose it
+pub fn distinct_files<'a>(paths: &[&'a Path]) -> Vec<&'a Path> {
+    let mut seen = HashSet::new();
+    paths
+        .iter()
+        .copied()
+        .filter(|p| !fs::symlink_metadata(p).is_ok_and(|m| m.file_type().is_symlink()))
+        // files that are gone are kept, so the db can catch up
+        .filter(|p| p.canonicalize().map_or(true, |p| seen.insert(p)))
+        .collect()
+}
+
+// where a file is moved to when quarantined, e.g. /photos/a.png -> <dir>/3/photos/a.png for batch 3
+pub fn quarantine_path(dir: &Path, batch: i64, path: &Path) -> PathBuf {
+    let relative: PathBuf = path
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect();
+    dir.join(batch.to_string()).join(relative)
+}
+
+// moves a file, creating dst's parent dirs & never overwriting dst
+pub fn move_file(src: &Path, dst: &Path) -> io::Result<()> {
+    if dst.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{:?} already exists", dst),
+        ));
+    }
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(src, dst) {
+        // rename doesn't work across filesystems, so copy & delete the original instead
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(src, dst)?;
+            fs::remove_file(src)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_policies() {
+        assert_eq!(parse_keep("shortest-path"), Ok(Keep::ShortestPath));
+        assert_eq!(
+            parse_keep("path-priority=/"),
+            Ok(Keep::PathPriority(PathBuf::from("/")))
+        );
+        assert!(parse_keep("largest").is_err());
+    }
+
+    #[test]
+    fn quarantine_keeps_the_full_path() {
+        assert_eq!(
+            quarantine_path(Path::new("/q"), 3, Path::new("/photos/a.png")),
+            PathBuf::from("/q/3/photos/a.png")
+        );
+    }
+
+    #[test]
+    fn keeps_by_path() {
+        let paths = [
+            Path::new("/b/long/name.png"),
+            Path::new("/a/x.png"),
+            Path::new("/b/y.png"),
+        ];
+        assert_eq!(keeper(&paths, &Keep::ShortestPath), 1);
+        assert_eq!(keeper(&paths, &Keep::PathPriority(PathBuf::from("/b"))), 0);
+        assert_eq!(keeper(&paths, &Keep::PathPriority(PathBuf::from("/c"))), 0);
+        // none of the files exist, so the first one wins the tie
+        assert_eq!(keeper(&paths, &Keep::Oldest), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leaves_out_symlinks_and_the_same_file_twice() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (file, link, copy) = (dir.join("a.png"), dir.join("b.png"), dir.join("c.png"));
+        fs::write(&file, "a").unwrap();
+        fs::write(&copy, "a").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        let same = dir.join(".").join("a.png");
+        let gone = dir.join("gone.png");
+        let paths = [
+            link.as_path(),
+            file.as_path(),
+            same.as_path(),
+            copy.as_path(),
+            gone.as_path(),
+        ];
+        assert_eq!(
+            distinct_files(&paths),
+            vec![file.as_path(), copy.as_path(), gone.as_path()]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::hash::phash;
+use crate::montage;
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgb, RgbImage};
+
+// both images are scaled to this size, like phash & verify they're compared ignoring the aspect ratio
+const SIZE: u32 = 256;
+const CELL: u32 = SIZE / 8;
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+const GRAY: Rgb<u8> = Rgb([160, 160, 160]);
+const RED: Rgb<u8> = Rgb([220, 40, 40]);
+
+// a sheet w/ both images, the heatmap of their difference, both phash bit grids & the bits that differ
+pub fn render(a: &DynamicImage, b: &DynamicImage) -> RgbImage {
+    let (bits_a, bits_b) = (phash::bits(a.clone()), phash::bits(b.clone()));
+    let a = a.resize_exact(SIZE, SIZE, FilterType::Triangle);
+    let b = b.resize_exact(SIZE, SIZE, FilterType::Triangle);
+    // the first bit doesn't make it into the hash, see phash::bits
+    let differing = (1..64).filter(|i| bits_a[*i] != bits_b[*i]).count();
+    let tiles = [
+        heatmap(&a.to_rgb8(), &b.to_rgb8()),
+        grid(|i| if bits_a[i] { WHITE } else { BLACK }),
+        grid(|i| if bits_b[i] { WHITE } else { BLACK }),
+        grid(|i| match i {
+            0 => GRAY,
+            _ if bits_a[i] != bits_b[i] => RED,
+            _ => WHITE,
+        }),
+    ];
+    let [heat, grid_a, grid_b, xor] = tiles.map(|t| Some(DynamicImage::ImageRgb8(t)));
+    let images = [Some(a), Some(b), heat, grid_a, grid_b, xor];
+    let labels = [
+        "img1",
+        "img2",
+        "pixel difference",
+        "phash img1",
+        "phash img2",
+        &format!("differing bits={}", differing),
+    ]
+    .map(|l| vec![l.to_string()]);
+    montage::sheet(&images, &labels)
+}
+
+// the largest channel difference of every pixel, from black (same) over red & yellow to white
+fn heatmap(a: &RgbImage, b: &RgbImage) -> RgbImage {
+    RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let diff = (0..3).map(|c| pa[c].abs_diff(pb[c])).max().unwrap_or(0) as f32 / 255.0;
+        let channel = |offset: f32| ((diff * 3.0 - offset).clamp(0.0, 1.0) * 255.0) as u8;
+        Rgb([channel(0.0), channel(1.0), channel(2.0)])
+    })
+}
+
+// 8x8 cells colored by their index (row by row), separated by gray lines
+fn grid(color: impl Fn(usize) -> Rgb<u8>) -> RgbImage {
+    RgbImage::from_fn(SIZE, SIZE, |x, y| {
+        if x % CELL == 0 || y % CELL == 0 {
+            return GRAY;
+        }
+        color((y / CELL * 8 + x / CELL) as usize)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heatmap_goes_from_black_to_white() {
+        let a = RgbImage::from_fn(3, 1, |x, _| Rgb([[0, 0, 0][x as usize]; 3]));
+        let b = RgbImage::from_fn(3, 1, |x, _| Rgb([[0, 85, 255][x as usize]; 3]));
+        let heat = heatmap(&a, &b);
+        assert_eq!(*heat.get_pixel(0, 0), BLACK);
+        assert_eq!(*heat.get_pixel(1, 0), Rgb([255, 0, 0]));
+        assert_eq!(*heat.get_pixel(2, 0), WHITE);
+    }
+
+    #[test]
+    fn grid_cells_are_row_by_row() {
+        let grid = grid(|i| if i == 9 { RED } else { WHITE });
+        assert_eq!(*grid.get_pixel(CELL + CELL / 2, CELL + CELL / 2), RED);
+        assert_eq!(*grid.get_pixel(CELL / 2, CELL + CELL / 2), WHITE);
+        assert_eq!(*grid.get_pixel(CELL, 5), GRAY);
+    }
+}
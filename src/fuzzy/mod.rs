@@ -0,0 +1,254 @@
+use crate::db;
+use crate::db::ImgData;
+use crate::hash;
+use crate::hash::crop::Region;
+use crate::mih::MultiIndex;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub struct Pair<'a> {
+    pub a: &'a ImgData,
+    pub b: &'a ImgData,
+    pub dist: u8,
+    // None when either image was scanned before color signatures were stored
+    pub color_dist: Option<u32>,
+}
+
+impl Pair<'_> {
+    // the decoded pixel data is identical, not just similar
+    pub fn is_exact(&self) -> bool {
+        !self.a.sha256.is_empty() && self.a.sha256 == self.b.sha256
+    }
+
+    pub fn color_differs(&self, max_color_dist: u32) -> bool {
+        self.color_dist.is_some_and(|d| d > max_color_dist)
+    }
+}
+
+// multi-index over the phashes of the images, the ids are the images_id of each image. searches
+// up to max_dist only need exact chunk lookups, further ones still work but check more hashes
+pub fn index(images: &[ImgData], max_dist: u8) -> MultiIndex {
+    let mut index = MultiIndex::new(max_dist);
+    for img in images {
+        if let Some(hash) = parse(&img.phash, img) {
+            index.insert(hash, img.images_id as usize);
+        }
+    }
+    index
+}
+
+// same as index, but reuses the hashes cached next to the db. if images were only added to the same
+// db since they were saved they are inserted into the cached index, any other change rebuilds it
+pub fn cached_index(images: &[ImgData], max_dist: u8) -> MultiIndex {
+    let path = db::index_path();
+    let generation = match db::generation() {
+        Ok(generation) => generation,
+        Err(err) => {
+            warn!("Can't read db generation, not caching the index: {}", err);
+            return index(images, max_dist);
+        }
+    };
+
+    let idx = match MultiIndex::load(&path, max_dist) {
+        Ok((cached, idx)) if cached == generation => {
+            debug!("using cached index generation={:?}", generation);
+            return idx;
+        }
+        Ok((cached, _)) if cached.0 != generation.0 => {
+            debug!("rebuilding index cached for another db");
+            index(images, max_dist)
+        }
+        Ok((cached, mut idx)) => {
+            let current: HashMap<usize, u64> = images
+                .iter()
+                .filter_map(|img| Some((img.images_id as usize, img.phash.parse().ok()?)))
+                .collect();
+            let entries: HashMap<usize, u64> = idx.entries().collect();
+            if entries.iter().all(|(id, hash)| current.get(id) == Some(hash)) {
+                debug!("updating cached index generation={} -> {}", cached.1, generation.1);
+                for (id, hash) in &current {
+                    if !entries.contains_key(id) {
+                        idx.insert(*hash, *id);
+                    }
+                }
+                idx
+            } else {
+                debug!("rebuilding stale index generation={} -> {}", cached.1, generation.1);
+                index(images, max_dist)
+            }
+        }
+        Err(err) => {
+            debug!("rebuilding index, can't load {:?}: {}", path, err);
+            index(images, max_dist)
+        }
+    };
+    if let Err(err) = idx.save(&path, generation) {
+        warn!("Failed to cache index at {:?}: {}", path, err);
+    }
+    idx
+}
+
+fn positions(images: &[ImgData]) -> HashMap<usize, usize> {
+    images
+        .iter()
+        .enumerate()
+        .map(|(i, img)| (img.images_id as usize, i))
+        .collect()
+}
+
+// every pair of images within max_dist of each other, closest first. index must be built from images
+pub fn pairs<'a>(images: &'a [ImgData], index: &MultiIndex, max_dist: u8) -> Vec<Pair<'a>> {
+    let colors: Vec<Option<u64>> = images.iter().map(|img| img.color.parse().ok()).collect();
+    let positions = positions(images);
+
+    let mut results = Vec::new();
+    for (i, img) in images.iter().enumerate() {
+        let Ok(x) = img.phash.parse() else { continue };
+        for (id, dist) in index.find(x, max_dist) {
+            let Some(&j) = positions.get(&id) else { continue };
+            // only keep each pair once
+            if j <= i {
+                continue;
+            }
+            let color_dist = match (colors[i], colors[j]) {
+                (Some(c1), Some(c2)) => Some(hash::color::dist(c1, c2)),
+                _ => None,
+            };
+            results.push(Pair {
+                a: &images[i],
+                b: &images[j],
+                dist,
+                color_dist,
+            });
+        }
+    }
+    results.sort_by(|x, y| (x.dist, &x.a.path, &x.b.path).cmp(&(y.dist, &y.a.path, &y.b.path)));
+    results
+}
+
+// the k images closest to the given phash, closest first. index must be built from images
+pub fn nearest<'a>(images: &'a [ImgData], index: &MultiIndex, phash: u64, k: usize) -> Vec<(&'a ImgData, u8)> {
+    let positions = positions(images);
+    // ask for one extra per image that might have been filtered out of images
+    let missing = index.entries().count().saturating_sub(images.len());
+    let mut results: Vec<(&ImgData, u8)> = index
+        .nearest(phash, k + missing)
+        .into_iter()
+        .filter_map(|(id, dist)| Some((&images[*positions.get(&id)?], dist)))
+        .collect();
+    results.sort_by_key(|(img, dist)| (*dist, &img.path));
+    results.truncate(k);
+    results
+}
+
+// the images within max_dist of the given phash, closest first. index must be built from images
+pub fn within<'a>(images: &'a [ImgData], index: &MultiIndex, phash: u64, max_dist: u8) -> Vec<(&'a ImgData, u8)> {
+    let positions = positions(images);
+    let mut results: Vec<(&ImgData, u8)> = index
+        .find(phash, max_dist)
+        .into_iter()
+        .filter_map(|(id, dist)| Some((&images[*positions.get(&id)?], dist)))
+        .collect();
+    results.sort_by_key(|(img, dist)| (*dist, &img.path));
+    results
+}
+
+// an image that looks like a sub-region of another
+#[derive(Debug)]
+pub struct Crop<'a> {
+    pub parent: &'a ImgData,
+    pub child: &'a ImgData,
+    pub region: &'a Region,
+    pub dist: u8,
+}
+
+// matches the full phash of every image against the region hashes of every other image.
+// images that are already near duplicates of each other as a whole aren't reported as crops
+pub fn crops<'a>(images: &'a [ImgData], regions: &'a [(PathBuf, Region)], max_dist: u8) -> Vec<Crop<'a>> {
+    let phashes: HashMap<&PathBuf, (&ImgData, u64)> = images
+        .iter()
+        .filter_map(|img| Some((&img.path, (img, parse(&img.phash, img)?))))
+        .collect();
+    let mut index = MultiIndex::new(max_dist);
+    for (i, (_, region)) in regions.iter().enumerate() {
+        index.insert(region.hash, i);
+    }
+
+    // keep only the closest region for each (parent, child)
+    let mut best: HashMap<(&PathBuf, &PathBuf), Crop> = HashMap::new();
+    for child in images {
+        let Some(&(_, x)) = phashes.get(&child.path) else {
+            continue;
+        };
+        for (i, dist) in index.find(x, max_dist) {
+            let (path, region) = &regions[i];
+            if *path == child.path {
+                continue;
+            }
+            let Some(&(parent, y)) = phashes.get(path) else {
+                continue;
+            };
+            if hash::hamming_dist_u64(x, y) <= max_dist {
+                continue;
+            }
+            let key = (path, &child.path);
+            if best.get(&key).is_none_or(|c| dist < c.dist) {
+                best.insert(
+                    key,
+                    Crop {
+                        parent,
+                        child,
+                        region,
+                        dist,
+                    },
+                );
+            }
+        }
+    }
+
+    let mut results: Vec<Crop> = best.into_values().collect();
+    results.sort_by(|a, b| (a.dist, &a.parent.path, &a.child.path).cmp(&(b.dist, &b.parent.path, &b.child.path)));
+    results
+}
+
+#[derive(Debug)]
+pub struct AnimatedPair<'a> {
+    pub a: &'a PathBuf,
+    pub b: &'a PathBuf,
+    pub frames_a: usize,
+    pub frames_b: usize,
+    pub similarity: f32,
+}
+
+// compares the frame sequences of every animated image against every other one
+pub fn animated_pairs(animated: &[(PathBuf, Vec<u64>)], max_dist: u8, min_similarity: f32) -> Vec<AnimatedPair<'_>> {
+    let mut results = Vec::new();
+    for (i, (a, frames_a)) in animated.iter().enumerate() {
+        for (b, frames_b) in &animated[i + 1..] {
+            let similarity = hash::frames::similarity(frames_a, frames_b, max_dist);
+            if similarity >= min_similarity {
+                results.push(AnimatedPair {
+                    a,
+                    b,
+                    frames_a: frames_a.len(),
+                    frames_b: frames_b.len(),
+                    similarity,
+                });
+            }
+        }
+    }
+    results.sort_by(|x, y| y.similarity.total_cmp(&x.similarity));
+    results
+}
+
+fn parse(hash: &str, img: &ImgData) -> Option<u64> {
+    match hash.parse() {
+        Ok(val) => Some(val),
+        Err(err) => {
+            warn!("Ignoring unparsable phash for {:?}: {}", img.path, err);
+            None
+        }
+    }
+}
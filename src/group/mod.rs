@@ -0,0 +1,346 @@
+use crate::db::HashMatch;
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Linkage {
+    /// Any chain of matches joins images into one group
+    Single,
+    /// Every member of a group must match every other member, avoids chaining unrelated images
+    Complete,
+}
+
+#[derive(Debug)]
+pub struct Member {
+    pub path: PathBuf,
+    // how this member has to be transformed to equal the group's first member (exact groups only)
+    pub transformation: Option<String>,
+    // distance to the group's first member, None if it's only linked through other members
+    pub dist: Option<u8>,
+}
+
+#[derive(Debug)]
+pub struct Group {
+    pub id: usize,
+    // the kind of hash that matched, "sha256" or "phash"
+    pub kind: String,
+    pub members: Vec<Member>,
+}
+
+impl Group {
+    // true if different members live under each of the two roots
+    pub fn spans(&self, a: &Path, b: &Path) -> bool {
+        self.members.iter().enumerate().any(|(i, m1)| {
+            m1.path.starts_with(a)
+                && self
+                    .members
+                    .iter()
+                    .enumerate()
+                    .any(|(j, m2)| i != j && m2.path.starts_with(b))
+        })
+    }
+}
+
+// a match between two images
+#[derive(Debug, Clone, Copy)]
+pub struct Edge<'a> {
+    pub a: &'a Path,
+    pub b: &'a Path,
+    pub dist: u8,
+}
+
+// groups images that share any sha256 hash, regardless of orientation
+pub fn exact_groups(matches: &[HashMatch]) -> Vec<Group> {
+    let mut by_hash: BTreeMap<&str, Vec<&Path>> = BTreeMap::new();
+    // path -> hash -> kinds of that image w/ that hash
+    let mut kinds: HashMap<&Path, HashMap<&str, Vec<&str>>> = HashMap::new();
+    for m in matches {
+        by_hash.entry(&m.hash).or_default().push(&m.path);
+        kinds
+            .entry(&m.path)
+            .or_default()
+            .entry(&m.hash)
+            .or_default()
+            .push(&m.kind);
+    }
+
+    let mut edges = Vec::new();
+    for paths in by_hash.values() {
+        for pair in paths.windows(2) {
+            if pair[0] != pair[1] {
+                edges.push(Edge {
+                    a: pair[0],
+                    b: pair[1],
+                    dist: 0,
+                });
+            }
+        }
+    }
+
+    let mut groups = to_groups(cluster(&edges, Linkage::Single, &[]), "sha256", |_, _| Some(0));
+    for group in &mut groups {
+        let root = &kinds[group.members[0].path.as_path()];
+        // the hash of the root image as it's stored on disk, or of its bytes if only those matched
+        let root_hash = root
+            .iter()
+            .find(|(_, k)| k.contains(&"sha256 imgdata"))
+            .or_else(|| root.iter().find(|(_, k)| k.contains(&"sha256 sha256")))
+            .map(|(h, _)| *h);
+        for member in &mut group.members {
+            let member_kinds = &kinds[member.path.as_path()];
+            member.transformation = Some(
+                root_hash
+                    .and_then(|h| member_kinds.get(h))
+                    .and_then(|k| k.first())
+                    .map(|k| transformation(k))
+                    .unwrap_or_else(|| "transitive".to_string()),
+            );
+        }
+    }
+    groups
+}
+
+// groups near duplicates, the dist of each member is its distance to the group's first member. the
+// images of each apart pair never end up in the same group
+pub fn fuzzy_groups<'a>(edges: &[Edge<'a>], linkage: Linkage, apart: &[(&'a Path, &'a Path)]) -> Vec<Group> {
+    let dists: HashMap<(&Path, &Path), u8> = edges
+        .iter()
+        .flat_map(|e| [((e.a, e.b), e.dist), ((e.b, e.a), e.dist)])
+        .collect();
+    to_groups(cluster(edges, linkage, apart), "phash", |a, b| {
+        if a == b {
+            Some(0)
+        } else {
+            dists.get(&(a, b)).copied()
+        }
+    })
+}
+
+// "sha256 imgdata flipv rot90" -> "flipv rot90", "sha256 imgdata" & "sha256 sha256" -> "none"
+fn transformation(kind: &str) -> String {
+    if kind == "sha256 sha256" {
+        return "none".to_string();
+    }
+    let t = kind
+        .trim_start_matches("sha256")
+        .trim()
+        .trim_start_matches("imgdata")
+        .trim();
+    if t.is_empty() {
+        "none".to_string()
+    } else {
+        t.to_string()
+    }
+}
+
+fn to_groups<'a>(
+    clusters: Vec<Vec<&'a Path>>,
+    kind: &str,
+    dist: impl Fn(&'a Path, &'a Path) -> Option<u8>,
+) -> Vec<Group> {
+    clusters
+        .into_iter()
+        .enumerate()
+        .map(|(i, paths)| Group {
+            id: i + 1,
+            kind: kind.to_string(),
+            members: paths
+                .iter()
+                .map(|p| Member {
+                    path: p.to_path_buf(),
+                    transformation: None,
+                    dist: dist(paths[0], p),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+// clusters of 2+ images, members sorted by path & clusters sorted by size then first path. two
+// clusters aren't merged if that would put both images of an apart pair together
+pub fn cluster<'a>(edges: &[Edge<'a>], linkage: Linkage, apart: &[(&'a Path, &'a Path)]) -> Vec<Vec<&'a Path>> {
+    let mut nodes: Vec<&Path> = edges.iter().flat_map(|e| [e.a, e.b]).collect();
+    nodes.sort();
+    nodes.dedup();
+    let index: HashMap<&Path, usize> = nodes.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+    let linked: HashSet<(usize, usize)> = edges
+        .iter()
+        .flat_map(|e| [(index[e.a], index[e.b]), (index[e.b], index[e.a])])
+        .collect();
+    // pairs w/o an edge to either image can't end up together anyway
+    let apart: HashSet<(usize, usize)> = apart
+        .iter()
+        .filter_map(|(a, b)| Some((*index.get(a)?, *index.get(b)?)))
+        .flat_map(|(a, b)| [(a, b), (b, a)])
+        .collect();
+
+    let mut sorted = edges.to_vec();
+    sorted.sort_by_key(|e| e.dist);
+
+    let mut uf = UnionFind::new(nodes.len());
+    let mut members: Vec<Vec<usize>> = (0..nodes.len()).map(|i| vec![i]).collect();
+    for e in sorted {
+        let (x, y) = (uf.find(index[e.a]), uf.find(index[e.b]));
+        if x == y {
+            continue;
+        }
+        if linkage == Linkage::Complete
+            && !members[x]
+                .iter()
+                .all(|u| members[y].iter().all(|v| linked.contains(&(*u, *v))))
+        {
+            continue;
+        }
+        if !apart.is_empty()
+            && members[x]
+                .iter()
+                .any(|u| members[y].iter().any(|v| apart.contains(&(*u, *v))))
+        {
+            continue;
+        }
+        let root = uf.union(x, y);
+        let other = if root == x { y } else { x };
+        let moved = std::mem::take(&mut members[other]);
+        members[root].extend(moved);
+    }
+
+    let mut clusters: Vec<Vec<&Path>> = members
+        .into_iter()
+        .filter(|m| m.len() > 1)
+        .map(|m| {
+            let mut paths: Vec<&Path> = m.iter().map(|i| nodes[*i]).collect();
+            paths.sort();
+            paths
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(b[0])));
+    clusters
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> UnionFind {
+        UnionFind {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // path compression
+        let mut x = x;
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    // returns the new root, both args need to be roots
+    fn union(&mut self, x: usize, y: usize) -> usize {
+        let (root, child) = if self.rank[x] >= self.rank[y] { (x, y) } else { (y, x) };
+        self.parent[child] = root;
+        if self.rank[x] == self.rank[y] {
+            self.rank[root] += 1;
+        }
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge<'a>(a: &'a str, b: &'a str, dist: u8) -> Edge<'a> {
+        Edge {
+            a: Path::new(a),
+            b: Path::new(b),
+            dist,
+        }
+    }
+
+    #[test]
+    fn single_linkage_chains() {
+        let edges = [edge("/a", "/b", 1), edge("/b", "/c", 1), edge("/x", "/y", 0)];
+        let clusters = cluster(&edges, Linkage::Single, &[]);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0], vec![Path::new("/a"), Path::new("/b"), Path::new("/c")]);
+    }
+
+    #[test]
+    fn apart_pairs_dont_chain() {
+        let edges = [edge("/a", "/b", 1), edge("/b", "/c", 2)];
+        let apart = [(Path::new("/c"), Path::new("/a"))];
+        let clusters = cluster(&edges, Linkage::Single, &apart);
+        assert_eq!(clusters, vec![vec![Path::new("/a"), Path::new("/b")]]);
+    }
+
+    #[test]
+    fn complete_linkage_doesnt_chain() {
+        let edges = [edge("/a", "/b", 1), edge("/b", "/c", 2)];
+        let clusters = cluster(&edges, Linkage::Complete, &[]);
+        assert_eq!(clusters, vec![vec![Path::new("/a"), Path::new("/b")]]);
+    }
+
+    #[test]
+    fn complete_linkage_merges_cliques() {
+        let edges = [edge("/a", "/b", 1), edge("/b", "/c", 2), edge("/a", "/c", 3)];
+        assert_eq!(cluster(&edges, Linkage::Complete, &[]).len(), 1);
+    }
+
+    #[test]
+    fn spans_needs_a_member_under_each_root() {
+        let edges = [
+            edge("/camera/1.jpg", "/archive/1.jpg", 0),
+            edge("/camera/1.jpg", "/camera/2.jpg", 0),
+        ];
+        let group = &fuzzy_groups(&edges, Linkage::Single, &[])[0];
+        assert!(group.spans(Path::new("/camera"), Path::new("/archive")));
+        assert!(group.spans(Path::new("/archive"), Path::new("/camera")));
+        assert!(!group.spans(Path::new("/camera"), Path::new("/arch")));
+    }
+
+    #[test]
+    fn exact_groups_w_transformation() {
+        let m = |path: &str, kind: &str, hash: &str| HashMatch {
+            path: PathBuf::from(path),
+            kind: kind.to_string(),
+            hash: hash.to_string(),
+        };
+        let matches = [
+            m("/a", "sha256 imgdata", "h1"),
+            m("/a", "sha256 imgdata rot90", "h2"),
+            m("/b", "sha256 imgdata rot270", "h1"),
+            m("/b", "sha256 imgdata", "h2"),
+        ];
+        let groups = exact_groups(&matches);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].members[0].transformation.as_deref(), Some("none"));
+        assert_eq!(groups[0].members[1].transformation.as_deref(), Some("rot270"));
+    }
+
+    #[test]
+    fn exact_groups_on_file_bytes_only() {
+        let m = |path: &str| HashMatch {
+            path: PathBuf::from(path),
+            kind: "sha256 sha256".to_string(),
+            hash: "h1".to_string(),
+        };
+        let groups = exact_groups(&[m("/a.gif"), m("/b.gif")]);
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0]
+            .members
+            .iter()
+            .all(|m| m.transformation.as_deref() == Some("none")));
+    }
+}
@@ -0,0 +1,106 @@
+use super::flatten;
+use super::ImgHash;
+use super::ImgHashKind;
+use image::{DynamicImage, ImageError, ImageReader, Rgb};
+use std::path::Path;
+
+// 6 hue sectors + light & dark achromatic bins, one byte each so it packs into a u64
+const BINS: usize = 8;
+const SIZE: u32 = 16;
+// below this saturation a pixel is treated as grey
+const MIN_SATURATION: f32 = 0.2;
+
+pub fn hash_path(path: &Path, background: Rgb<u8>) -> Result<ImgHash, ImageError> {
+    let img = flatten(ImageReader::open(path)?.with_guessed_format()?.decode()?, background);
+    Ok(from_img(path, &img))
+}
+
+// img needs to be flattened already
+pub fn from_img(path: &Path, img: &DynamicImage) -> ImgHash {
+    ImgHash {
+        path: path.to_path_buf(),
+        kind: ImgHashKind::Color,
+        hash: hash(img).to_string(),
+    }
+}
+
+// coarse HSV histogram of the image, each bin is the fraction of pixels scaled to 0..=255
+pub fn hash(img: &DynamicImage) -> u64 {
+    let img = img
+        .resize_exact(SIZE, SIZE, image::imageops::FilterType::Triangle)
+        .into_rgb8();
+
+    let mut counts = [0u32; BINS];
+    for p in img.pixels() {
+        counts[bin(p.0)] += 1;
+    }
+
+    let total = SIZE * SIZE;
+    let mut signature: u64 = 0;
+    for (i, count) in counts.iter().enumerate() {
+        let byte = (count * 255 / total) as u64;
+        signature |= byte << (8 * i);
+    }
+    signature
+}
+
+// L1 distance between two signatures, ranges from 0 (same colors) to 510 (disjoint colors)
+pub fn dist(a: u64, b: u64) -> u32 {
+    (0..BINS)
+        .map(|i| {
+            let x = (a >> (8 * i)) & 0xff;
+            let y = (b >> (8 * i)) & 0xff;
+            x.abs_diff(y) as u32
+        })
+        .sum()
+}
+
+fn bin([r, g, b]: [u8; 3]) -> usize {
+    let max = r.max(g).max(b) as f32;
+    let min = r.min(g).min(b) as f32;
+    let chroma = max - min;
+    if max == 0.0 || chroma / max < MIN_SATURATION {
+        return if max >= 128.0 { 6 } else { 7 };
+    }
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let hue = if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    (hue as usize).min(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn solid(color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(32, 32, Rgb(color)))
+    }
+
+    #[test]
+    fn same_image_same_signature() {
+        let img = solid([200, 30, 30]);
+        assert_eq!(dist(hash(&img), hash(&img)), 0);
+    }
+
+    #[test]
+    fn grayscale_differs_from_color() {
+        let red = solid([200, 30, 30]);
+        let grey = DynamicImage::ImageLuma8(red.to_luma8());
+        assert_eq!(dist(hash(&red), hash(&grey)), 510);
+    }
+
+    #[test]
+    fn hue_bins() {
+        assert_eq!(bin([255, 0, 0]), 0);
+        assert_eq!(bin([0, 255, 0]), 2);
+        assert_eq!(bin([0, 0, 255]), 4);
+        assert_eq!(bin([250, 250, 250]), 6);
+        assert_eq!(bin([10, 10, 10]), 7);
+    }
+}
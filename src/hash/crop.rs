@@ -0,0 +1,115 @@
+use super::phash;
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+// window widths & heights as a percent of the parent image, every width is paired w/ every height so
+// crops that change the aspect ratio still line up w/ a window
+const SCALES: [u32; 4] = [50, 60, 70, 80];
+// distance in percent between the positions of the windows along each axis
+const STEP: u32 = 5;
+// the image is scaled down to this before cropping, each window is scaled down to 8x8 anyway
+const SIZE: u32 = 64;
+
+// a phash of a sub-region of an image, the region is in percent of the parent's dimensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub x: u8,
+    pub y: u8,
+    pub w: u8,
+    pub h: u8,
+    pub hash: u64,
+}
+
+// phashes overlapping windows of a few widths & heights so crops of the image can still be matched
+pub fn hash(img: &DynamicImage) -> Vec<Region> {
+    let small = img.resize_exact(SIZE, SIZE, FilterType::Triangle);
+    let mut regions = Vec::new();
+    for w in SCALES {
+        for h in SCALES {
+            for (x, y) in windows(w, h) {
+                let (px, py) = (SIZE * x / 100, SIZE * y / 100);
+                let (pw, ph) = ((SIZE * w / 100).max(1), (SIZE * h / 100).max(1));
+                regions.push(Region {
+                    x: x as u8,
+                    y: y as u8,
+                    w: w as u8,
+                    h: h as u8,
+                    hash: phash::hash(small.crop_imm(px, py, pw, ph)),
+                });
+            }
+        }
+    }
+    regions
+}
+
+// top left corner (in percent) of every w x h window, the last one on each axis is flush w/ the edge
+fn windows(w: u32, h: u32) -> Vec<(u32, u32)> {
+    let offsets = |scale: u32| {
+        let mut offsets: Vec<u32> = (0..=100 - scale).step_by(STEP as usize).collect();
+        if offsets.last() != Some(&(100 - scale)) {
+            offsets.push(100 - scale);
+        }
+        offsets
+    };
+    let xs = offsets(w);
+    offsets(h)
+        .into_iter()
+        .flat_map(|y| xs.iter().map(move |x| (*x, y)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn windows_stay_inside_image() {
+        for w in SCALES {
+            for h in SCALES {
+                for (x, y) in windows(w, h) {
+                    assert!(x + w <= 100);
+                    assert!(y + h <= 100);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn half_size_centered_window_exists() {
+        assert!(windows(50, 50).contains(&(25, 25)));
+    }
+
+    #[test]
+    fn finds_off_grid_crop_w_another_aspect_ratio() {
+        // soft blobs of different sizes, so only the windows around the crop look like it
+        let blobs: Vec<(f32, f32, f32)> = (0..24)
+            .map(|i| {
+                (
+                    (i * 73 % 200) as f32,
+                    (i * 131 % 200) as f32,
+                    (8 + i * 7 % 18) as f32 * if i % 2 == 0 { 1.0 } else { -1.0 },
+                )
+            })
+            .collect();
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(200, 200, |x, y| {
+            let v: f32 = blobs
+                .iter()
+                .map(|(bx, by, r)| {
+                    let d = (x as f32 - bx).powi(2) + (y as f32 - by).powi(2);
+                    100.0 * r.signum() * (-d / (r * r)).exp()
+                })
+                .sum();
+            let v = (128.0 + v) as u8;
+            Rgb([v, v, v])
+        }));
+        let crop = phash::hash(img.crop_imm(17, 41, 130, 110));
+        let best = hash(&img)
+            .into_iter()
+            .min_by_key(|r| (r.hash ^ crop).count_ones())
+            .unwrap();
+        assert!((best.hash ^ crop).count_ones() <= 5, "{:?}", best);
+        // the crop is at 8.5%, 20.5% of the image
+        assert!(best.x.abs_diff(8) <= 10 && best.y.abs_diff(20) <= 10, "{:?}", best);
+    }
+}
@@ -0,0 +1,118 @@
+use super::flatten;
+use super::hamming_dist_u64;
+use super::phash;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, Frames, ImageError, ImageFormat, ImageReader, Rgb};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// long animations are sampled down to this many frames
+const MAX_FRAMES: usize = 256;
+
+// phashes of every frame of an animated GIF/APNG/WebP, consecutive identical frames are collapsed.
+// returns an empty list for still images
+pub fn hash_path(path: &Path, background: Rgb<u8>) -> Result<Vec<u64>, ImageError> {
+    let Some(frames) = open_frames(path)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut hashes: Vec<u64> = Vec::new();
+    for frame in frames {
+        let img = DynamicImage::ImageRgba8(frame?.into_buffer());
+        let hash = phash::hash(flatten(img, background));
+        if hashes.last() != Some(&hash) {
+            hashes.push(hash);
+        }
+    }
+    if hashes.len() < 2 {
+        // every frame looks the same, so treat it like a still image
+        return Ok(Vec::new());
+    }
+    Ok(sample(hashes))
+}
+
+fn open_frames(path: &Path) -> Result<Option<Frames<'static>>, ImageError> {
+    let format = ImageReader::open(path)?.with_guessed_format()?.format();
+    let reader = BufReader::new(File::open(path)?);
+    let frames = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader)?.into_frames(),
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader)?;
+            if !decoder.is_apng()? {
+                return Ok(None);
+            }
+            decoder.apng()?.into_frames()
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(frames))
+}
+
+fn sample(hashes: Vec<u64>) -> Vec<u64> {
+    if hashes.len() <= MAX_FRAMES {
+        return hashes;
+    }
+    (0..MAX_FRAMES).map(|i| hashes[i * hashes.len() / MAX_FRAMES]).collect()
+}
+
+// longest common subsequence of frames, where two frames are equal if their phashes are within
+// max_dist, divided by the length of the shorter sequence. a trimmed copy scores 1.0
+pub fn similarity(a: &[u64], b: &[u64], max_dist: u8) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let mut prev = vec![0usize; b.len() + 1];
+    let mut curr = vec![0usize; b.len() + 1];
+    for x in a {
+        for (j, y) in b.iter().enumerate() {
+            curr[j + 1] = if hamming_dist_u64(*x, *y) <= max_dist {
+                prev[j] + 1
+            } else {
+                prev[j + 1].max(curr[j])
+            };
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()] as f32 / a.len().min(b.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trimmed_copy_is_similar() {
+        let full = [1, 0xff, 0xff00, 0xff0000, 0xff000000];
+        assert_eq!(similarity(&full, &full[1..4], 0), 1.0);
+    }
+
+    #[test]
+    fn shared_first_frame_is_not_similar() {
+        let a = [1, 0xff, 0xff00, 0xff0000];
+        let b = [1, 0xff << 32, 0xff00 << 32, 0xff0000 << 32];
+        assert_eq!(similarity(&a, &b, 2), 0.25);
+    }
+
+    #[test]
+    fn near_frames_match() {
+        assert_eq!(similarity(&[0b1000, 0xff00], &[0b1001, 0xff01], 1), 1.0);
+    }
+
+    #[test]
+    fn long_animations_are_sampled() {
+        let hashes: Vec<u64> = (0..1000).collect();
+        let sampled = sample(hashes);
+        assert_eq!(sampled.len(), MAX_FRAMES);
+        assert_eq!(sampled[0], 0);
+    }
+}
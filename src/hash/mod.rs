@@ -1,13 +1,18 @@
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
 use std::fmt;
 use std::num::ParseIntError;
 use std::path::PathBuf;
 
+pub mod color;
+pub mod crop;
+pub mod frames;
 pub mod phash;
 pub mod sha256;
 
 #[derive(Debug)]
 pub enum ImgHashKind {
     Phash,
+    Color,
     Sha256(String), // this describes the rotation & flip performed on the image
 }
 
@@ -15,6 +20,7 @@ impl fmt::Display for ImgHashKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ImgHashKind::Phash => write!(f, "phash"),
+            ImgHashKind::Color => write!(f, "color"),
             ImgHashKind::Sha256(s) => write!(f, "sha256 {}", s),
         }
     }
@@ -27,24 +33,53 @@ pub struct ImgHash {
     pub hash: String,
 }
 
+// composites transparent images onto a solid background so the hidden RGB values of fully
+// transparent pixels don't leak into the perceptual hashes
+pub fn flatten(img: DynamicImage, background: Rgb<u8>) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return img;
+    }
+    let rgba = img.into_rgba8();
+    let flat = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let a = a as u32;
+        let blend = |c: u8, bg: u8| ((c as u32 * a + bg as u32 * (255 - a) + 127) / 255) as u8;
+        Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    });
+    DynamicImage::ImageRgb8(flat)
+}
+
+// true if the image has an alpha channel that isn't fully opaque everywhere
+pub fn has_alpha(img: &DynamicImage) -> bool {
+    img.color().has_alpha() && img.pixels().any(|(_, _, p)| p.0[3] < 255)
+}
+
+// parses a background color given as hex, e.g. "ffffff" or "#000000"
+pub fn parse_rgb(s: &str) -> Result<Rgb<u8>, String> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("expected 6 hex digits, got {:?}", s));
+    }
+    let val = u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+    Ok(Rgb([(val >> 16) as u8, (val >> 8) as u8, val as u8]))
+}
+
 // counts the number of bits that are different using the hash as a number
 pub fn hamming_dist(a: ImgHash, b: ImgHash) -> Result<u8, ParseIntError> {
     let x: u64 = a.hash.parse()?;
     let y: u64 = b.hash.parse()?;
-    Ok(hamming_dist_internal(x, y))
+    Ok(hamming_dist_u64(x, y))
 }
 
-// counts the number of bits that are different
-fn hamming_dist_internal(mut a: u64, mut b: u64) -> u8 {
-    let mut count: u8 = 0;
-    while a > 0 && b > 0 {
-        if a & 1 != b & 1 {
-            count += 1;
-        }
-        a >>= 1; // div by 2
-        b >>= 1; // div by 2
-    }
-    count
+// counts the number of bits that are different. this used to stop counting as soon as either hash
+// ran out of set bits, so it under counted and broke the triangle inequality the phash index relies
+// on. distances (and so which images match at a given --max-dist) change for hashes w/ leading zeros
+pub fn hamming_dist_u64(a: u64, b: u64) -> u8 {
+    (a ^ b).count_ones() as u8
 }
 
 #[cfg(test)]
@@ -54,15 +89,66 @@ mod tests {
     #[test]
     fn hamming_dist_same() {
         let x = 0x8f8f978589f9f1c0;
-        assert_eq!(hamming_dist_internal(x, x), 0);
+        assert_eq!(hamming_dist_u64(x, x), 0);
     }
 
     #[test]
     fn hamming_dist_off_by_one() {
         let x = 0x8f8f978589f9f1c0; // last 4 bits are 0's
         let y = x + 1;
-        assert_eq!(hamming_dist_internal(x, y), 1);
+        assert_eq!(hamming_dist_u64(x, y), 1);
         let z = x + 8; // any pow of 2 should only change on bit (assuming no carry bit)
-        assert_eq!(hamming_dist_internal(x, z), 1);
+        assert_eq!(hamming_dist_u64(x, z), 1);
+    }
+
+    #[test]
+    fn hamming_dist_old_vs_new() {
+        // the old loop, kept here to show which distances changed
+        fn old(mut a: u64, mut b: u64) -> u8 {
+            let mut count: u8 = 0;
+            while a > 0 && b > 0 {
+                if a & 1 != b & 1 {
+                    count += 1;
+                }
+                a >>= 1;
+                b >>= 1;
+            }
+            count
+        }
+        // same while both hashes still have set bits
+        let x = 0x8f8f978589f9f1c0;
+        assert_eq!(old(x, x + 8), hamming_dist_u64(x, x + 8));
+        // but the old loop stopped at the highest set bit of the smaller one
+        assert_eq!((old(0b1011, 0), hamming_dist_u64(0b1011, 0)), (0, 3));
+        assert_eq!((old(0b1, 0b1011), hamming_dist_u64(0b1, 0b1011)), (0, 2));
+        assert_eq!((old(u64::MAX, 0), hamming_dist_u64(u64::MAX, 0)), (0, 64));
+        // which broke the triangle inequality: d(a, c) <= d(a, b) + d(b, c)
+        let (a, b, c) = (0b10, 0, 0b01);
+        assert!(old(a, c) > old(a, b) + old(b, c));
+        assert!(hamming_dist_u64(a, c) <= hamming_dist_u64(a, b) + hamming_dist_u64(b, c));
+    }
+
+    #[test]
+    fn flatten_transparent_onto_background() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 2, image::Rgba([10, 20, 30, 0])));
+        assert!(has_alpha(&img));
+        let flat = flatten(img, Rgb([255, 255, 255])).into_rgb8();
+        assert_eq!(flat.get_pixel(0, 0), &Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn opaque_alpha_is_not_meaningful() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 2, image::Rgba([10, 20, 30, 255])));
+        assert!(!has_alpha(&img));
+        assert_eq!(
+            flatten(img, Rgb([0, 0, 0])).into_rgb8().get_pixel(1, 1),
+            &Rgb([10, 20, 30])
+        );
+    }
+
+    #[test]
+    fn parse_background() {
+        assert_eq!(parse_rgb("#ff8000"), Ok(Rgb([255, 128, 0])));
+        assert!(parse_rgb("fff").is_err());
     }
 }
@@ -1,18 +1,39 @@
+use super::flatten;
 use super::ImgHash;
 use super::ImgHashKind;
-use image::{DynamicImage, ImageError, ImageReader};
+use image::{DynamicImage, ImageError, ImageReader, Rgb};
 use std::path::Path;
 
-pub fn hash_path(path: &Path) -> Result<ImgHash, ImageError> {
-    let img = ImageReader::open(path)?.with_guessed_format()?.decode()?;
-    Ok(ImgHash {
+pub fn hash_path(path: &Path, background: Rgb<u8>) -> Result<ImgHash, ImageError> {
+    let img = flatten(ImageReader::open(path)?.with_guessed_format()?.decode()?, background);
+    Ok(from_img(path, img))
+}
+
+// img needs to be flattened already
+pub fn from_img(path: &Path, img: DynamicImage) -> ImgHash {
+    ImgHash {
         path: path.to_path_buf(),
         kind: ImgHashKind::Phash,
         hash: hash(img).to_string(),
-    })
+    }
 }
 
 pub fn hash(img: DynamicImage) -> u64 {
+    // SAFETY this will overflow if width * height is anything over 64 bits
+    let mut average_hash: u64 = 0;
+    for bit in bits(img) {
+        // trace!("{} {:#b}", bit, average_hash);
+        if bit {
+            average_hash += 1;
+        }
+        average_hash <<= 1; // shift one from least to most significant
+    }
+    average_hash
+}
+
+// whether each pixel of the image scaled down to 8x8 is brighter than their average, row by row.
+// hash shifts the first bit out of the u64, so only the other 63 count
+pub fn bits(img: DynamicImage) -> [bool; 64] {
     // trace!("original dimensions {:?}", img.dimensions());
     // trace!("original color {:?}", img.color());
 
@@ -32,14 +53,9 @@ pub fn hash(img: DynamicImage) -> u64 {
     // trace!("average {:?}", avg);
 
     assert!(w * h == 64);
-    // SAFETY this will overflow if width * height is anything over 64 bits
-    let mut average_hash: u64 = 0;
-    for p in img.iter() {
-        // trace!("{} {:#b}", *p, average_hash);
-        if *p as u32 > avg {
-            average_hash += 1;
-        }
-        average_hash <<= 1; // shift one from least to most significant
+    let mut bits = [false; 64];
+    for (bit, p) in bits.iter_mut().zip(img.iter()) {
+        *bit = *p as u32 > avg;
     }
-    average_hash
+    bits
 }
@@ -1,13 +1,13 @@
 use super::ImgHash;
 use super::ImgHashKind;
-use image::{ImageError, ImageReader};
+use image::DynamicImage;
 use std::fs::read;
 use std::path::Path;
 
 // NOTE: hashing the bytes from a DynamicImage isn't the same as
 // hashing the bytes from a file on disk
-pub fn all_hashes_of_img_data(path: &Path) -> Result<Vec<ImgHash>, ImageError> {
-    let img = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+#[allow(clippy::vec_init_then_push)]
+pub fn all_hashes_of_img_data(path: &Path, img: &DynamicImage) -> Vec<ImgHash> {
     let mut results = Vec::new();
 
     results.push(ImgHash {
@@ -52,7 +52,7 @@ pub fn all_hashes_of_img_data(path: &Path) -> Result<Vec<ImgHash>, ImageError> {
         hash: hash(img.flipv().rotate270().into_bytes()),
     });
 
-    Ok(results)
+    results
 }
 
 // NOTE: hashing the bytes from a DynamicImage isn't the same as
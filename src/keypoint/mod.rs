@@ -0,0 +1,353 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageError, ImageReader};
+use std::cmp::Reverse;
+use std::path::Path;
+
+// images are scaled so their longest side is this many pixels before detecting corners,
+// this way the same image at different resolutions produces comparable keypoints
+const SIDE: u32 = 384;
+// FAST-9: a corner needs this many contiguous circle pixels brighter or darker than the center
+const FAST_ARC: usize = 9;
+const FAST_THRESHOLD: i16 = 20;
+const MAX_KEYPOINTS: usize = 500;
+// descriptor tests are sampled inside a square patch of this radius around the keypoint
+const PATCH_RADIUS: i32 = 15;
+// a match is kept only if it's clearly better than the second best candidate (Lowe's ratio test)
+const MAX_MATCH_DIST: u32 = 64;
+const RATIO: f32 = 0.8;
+const RANSAC_ITERATIONS: usize = 500;
+// max reprojection error (in pixels of the scaled image) for a match to be geometrically consistent
+const RANSAC_TOLERANCE: f32 = 6.0;
+
+// bresenham circle of radius 3 used by FAST
+const CIRCLE: [(i32, i32); 16] = [
+    (0, -3),
+    (1, -3),
+    (2, -2),
+    (3, -1),
+    (3, 0),
+    (3, 1),
+    (2, 2),
+    (1, 3),
+    (0, 3),
+    (-1, 3),
+    (-2, 2),
+    (-3, 1),
+    (-3, 0),
+    (-3, -1),
+    (-2, -2),
+    (-1, -3),
+];
+
+#[derive(Debug, Clone)]
+pub struct Keypoint {
+    pub x: f32,
+    pub y: f32,
+    pub descriptor: [u64; 4],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Report {
+    pub keypoints1: usize,
+    pub keypoints2: usize,
+    // matches that passed the ratio test
+    pub matches: usize,
+    // matches that agree on a single similarity transform between the images
+    pub consistent: usize,
+}
+
+pub fn compare_paths(a: &Path, b: &Path) -> Result<Report, ImageError> {
+    let a = ImageReader::open(a)?.with_guessed_format()?.decode()?;
+    let b = ImageReader::open(b)?.with_guessed_format()?.decode()?;
+    Ok(compare(&a, &b))
+}
+
+pub fn compare(a: &DynamicImage, b: &DynamicImage) -> Report {
+    let kp1 = detect(a);
+    let kp2 = detect(b);
+    let matches = match_descriptors(&kp1, &kp2);
+    let consistent = consistent_matches(&kp1, &kp2, &matches);
+    Report {
+        keypoints1: kp1.len(),
+        keypoints2: kp2.len(),
+        matches: matches.len(),
+        consistent,
+    }
+}
+
+// FAST corners w/ BRIEF-style 256 bit binary descriptors
+pub fn detect(img: &DynamicImage) -> Vec<Keypoint> {
+    let gray = prepare(img);
+    let (w, h) = gray.dimensions();
+    let border = (PATCH_RADIUS + 1) as u32;
+    if w <= 2 * border || h <= 2 * border {
+        return Vec::new();
+    }
+
+    let mut corners = Vec::new();
+    for y in border..h - border {
+        for x in border..w - border {
+            if let Some(score) = fast_score(&gray, x as i32, y as i32) {
+                corners.push((score, x, y));
+            }
+        }
+    }
+    let corners = non_max_suppression(corners, w, h);
+
+    let pattern = pattern();
+    corners
+        .into_iter()
+        .map(|(x, y)| Keypoint {
+            x: x as f32,
+            y: y as f32,
+            descriptor: describe(&gray, x as i32, y as i32, &pattern),
+        })
+        .collect()
+}
+
+fn prepare(img: &DynamicImage) -> GrayImage {
+    let img = img.resize(SIDE, SIDE, FilterType::Triangle);
+    // smooth so the descriptor's pixel comparisons aren't dominated by noise
+    img.blur(1.0).into_luma8()
+}
+
+fn fast_score(img: &GrayImage, x: i32, y: i32) -> Option<u32> {
+    let center = img.get_pixel(x as u32, y as u32).0[0] as i16;
+    let ring: Vec<i16> = CIRCLE
+        .iter()
+        .map(|(dx, dy)| img.get_pixel((x + dx) as u32, (y + dy) as u32).0[0] as i16)
+        .collect();
+
+    for sign in [1, -1] {
+        let mut run = 0;
+        // go around twice so arcs wrapping past the start are found
+        for i in 0..ring.len() * 2 {
+            if sign * (ring[i % ring.len()] - center) > FAST_THRESHOLD {
+                run += 1;
+                if run >= FAST_ARC {
+                    let score = ring.iter().map(|p| (p - center).unsigned_abs() as u32).sum();
+                    return Some(score);
+                }
+            } else {
+                run = 0;
+            }
+        }
+    }
+    None
+}
+
+// keeps the strongest corner in each 3x3 neighbourhood, then the strongest corners overall
+fn non_max_suppression(corners: Vec<(u32, u32, u32)>, w: u32, h: u32) -> Vec<(u32, u32)> {
+    let mut scores = vec![0u32; (w * h) as usize];
+    for (score, x, y) in &corners {
+        scores[(y * w + x) as usize] = *score;
+    }
+    let mut kept: Vec<(u32, u32, u32)> = corners
+        .into_iter()
+        .filter(|(score, x, y)| {
+            (y - 1..=y + 1)
+                .all(|ny| (x - 1..=x + 1).all(|nx| (nx == *x && ny == *y) || scores[(ny * w + nx) as usize] <= *score))
+        })
+        .collect();
+    kept.sort_by_key(|c| Reverse(c.0));
+    kept.truncate(MAX_KEYPOINTS);
+    kept.into_iter().map(|(_, x, y)| (x, y)).collect()
+}
+
+// offsets of the two pixels compared for one bit of a descriptor
+type Test = ((i32, i32), (i32, i32));
+
+// fixed pseudo random point pairs inside the patch, the same for every image
+fn pattern() -> Vec<Test> {
+    let mut rng = Lcg(0x2545f4914f6cdd1d);
+    let mut point = || {
+        let span = (2 * PATCH_RADIUS + 1) as u64;
+        let x = (rng.next() % span) as i32 - PATCH_RADIUS;
+        let y = (rng.next() % span) as i32 - PATCH_RADIUS;
+        (x, y)
+    };
+    (0..256).map(|_| (point(), point())).collect()
+}
+
+fn describe(img: &GrayImage, x: i32, y: i32, pattern: &[Test]) -> [u64; 4] {
+    let mut descriptor = [0u64; 4];
+    for (i, ((x1, y1), (x2, y2))) in pattern.iter().enumerate() {
+        let p1 = img.get_pixel((x + x1) as u32, (y + y1) as u32).0[0];
+        let p2 = img.get_pixel((x + x2) as u32, (y + y2) as u32).0[0];
+        if p1 < p2 {
+            descriptor[i / 64] |= 1 << (i % 64);
+        }
+    }
+    descriptor
+}
+
+fn descriptor_dist(a: &[u64; 4], b: &[u64; 4]) -> u32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+// brute force nearest neighbour w/ ratio test & cross check, returns (index in a, index in b)
+fn match_descriptors(a: &[Keypoint], b: &[Keypoint]) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    for (i, kp) in a.iter().enumerate() {
+        let Some(j) = nearest(kp, b) else { continue };
+        // only keep matches that are also the best the other way around so the result is symmetric
+        if nearest(&b[j], a) == Some(i) {
+            matches.push((i, j));
+        }
+    }
+    matches
+}
+
+fn nearest(kp: &Keypoint, candidates: &[Keypoint]) -> Option<usize> {
+    let mut best = (u32::MAX, 0);
+    let mut second = u32::MAX;
+    for (j, other) in candidates.iter().enumerate() {
+        let d = descriptor_dist(&kp.descriptor, &other.descriptor);
+        if d < best.0 {
+            second = best.0;
+            best = (d, j);
+        } else if d < second {
+            second = d;
+        }
+    }
+    if best.0 <= MAX_MATCH_DIST && (best.0 as f32) < RATIO * second as f32 {
+        Some(best.1)
+    } else {
+        None
+    }
+}
+
+// RANSAC over similarity transforms (scale, rotation & translation), two matches define a candidate
+fn consistent_matches(a: &[Keypoint], b: &[Keypoint], matches: &[(usize, usize)]) -> usize {
+    if matches.len() < 2 {
+        return matches.len();
+    }
+    let mut rng = Lcg(0x9e3779b97f4a7c15);
+    let mut best = 1;
+    for _ in 0..RANSAC_ITERATIONS {
+        let i = (rng.next() % matches.len() as u64) as usize;
+        let j = (rng.next() % matches.len() as u64) as usize;
+        if i == j {
+            continue;
+        }
+        let Some(t) = Similarity::from_pairs(
+            (&a[matches[i].0], &b[matches[i].1]),
+            (&a[matches[j].0], &b[matches[j].1]),
+        ) else {
+            continue;
+        };
+        let inliers = matches
+            .iter()
+            .filter(|(m1, m2)| {
+                let (x, y) = t.apply(a[*m1].x, a[*m1].y);
+                let (dx, dy) = (x - b[*m2].x, y - b[*m2].y);
+                (dx * dx + dy * dy).sqrt() <= RANSAC_TOLERANCE
+            })
+            .count();
+        best = best.max(inliers);
+    }
+    best
+}
+
+// x' = a*x - b*y + tx, y' = b*x + a*y + ty
+struct Similarity {
+    a: f32,
+    b: f32,
+    tx: f32,
+    ty: f32,
+}
+
+impl Similarity {
+    fn from_pairs((p1, q1): (&Keypoint, &Keypoint), (p2, q2): (&Keypoint, &Keypoint)) -> Option<Similarity> {
+        let (dx, dy) = (p2.x - p1.x, p2.y - p1.y);
+        let (ex, ey) = (q2.x - q1.x, q2.y - q1.y);
+        let norm = dx * dx + dy * dy;
+        if norm < 1.0 {
+            return None;
+        }
+        let a = (dx * ex + dy * ey) / norm;
+        let b = (dx * ey - dy * ex) / norm;
+        Some(Similarity {
+            a,
+            b,
+            tx: q1.x - (a * p1.x - b * p1.y),
+            ty: q1.y - (b * p1.x + a * p1.y),
+        })
+    }
+
+    fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x - self.b * y + self.tx, self.b * x + self.a * y + self.ty)
+    }
+}
+
+// small deterministic generator so results are reproducible between runs
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, RgbImage};
+
+    // scattered squares give plenty of corners
+    fn checkers() -> DynamicImage {
+        let img = GrayImage::from_fn(200, 200, |x, y| {
+            let cell = (x / 17 + y / 23 + (x * y / 311)) % 3;
+            Luma([[20, 120, 230][cell as usize]])
+        });
+        DynamicImage::ImageLuma8(img)
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let report = compare(&checkers(), &checkers());
+        assert!(report.keypoints1 > 0);
+        assert!(report.consistent >= report.matches / 2);
+    }
+
+    #[test]
+    fn shifted_image_still_matches() {
+        let img = checkers();
+        let shifted = img.crop_imm(20, 10, 180, 190);
+        let report = compare(&img, &shifted);
+        assert!(report.consistent > 10);
+    }
+
+    #[test]
+    fn flat_image_has_no_keypoints() {
+        let flat = DynamicImage::ImageRgb8(RgbImage::new(100, 100));
+        assert!(detect(&flat).is_empty());
+    }
+
+    #[test]
+    fn circle_is_the_radius_3_ring() {
+        for (i, (x, y)) in CIRCLE.iter().enumerate() {
+            assert!((8..=10).contains(&(x * x + y * y)), "{:?} is off the ring", (x, y));
+            assert!(!CIRCLE[..i].contains(&(*x, *y)), "{:?} is there twice", (x, y));
+            // the segment test needs neighbors in the array to be neighbors on the ring
+            let (nx, ny) = CIRCLE[(i + 1) % CIRCLE.len()];
+            assert_eq!((nx - x).abs().max((ny - y).abs()), 1);
+        }
+    }
+
+    #[test]
+    fn similarity_from_translation() {
+        let kp = |x, y| Keypoint {
+            x,
+            y,
+            descriptor: [0; 4],
+        };
+        let t = Similarity::from_pairs((&kp(0.0, 0.0), &kp(5.0, 7.0)), (&kp(10.0, 0.0), &kp(15.0, 7.0))).unwrap();
+        let (x, y) = t.apply(3.0, 4.0);
+        assert!((x - 8.0).abs() < 1e-4 && (y - 11.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,186 @@
+use clap::ValueEnum;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Mode {
+    /// Both paths share the same data, only works within one filesystem
+    Hardlink,
+    /// Copy on write clone of the data, needs a filesystem that supports it (btrfs, xfs, ...)
+    Reflink,
+    /// The copy becomes a symlink to the file that's kept
+    Symlink,
+}
+
+// replaces dup w/ a link to keeper. returns false if dup already is one. the link is created next to
+// dup first & then renamed over it, so dup is never missing if something fails
+pub fn replace(keeper: &Path, dup: &Path, mode: Mode) -> io::Result<bool> {
+    // keeper could be a symlink to dup from an earlier run, linking to it would lose the data
+    let keeper = &keeper.canonicalize()?;
+    if *keeper == dup.canonicalize()? || already_linked(keeper, dup, mode)? {
+        return Ok(false);
+    }
+    if !same_contents(keeper, dup)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} & {:?} differ", keeper, dup),
+        ));
+    }
+    if mode == Mode::Hardlink && device(keeper)? != device(dup)? {
+        return Err(io::Error::new(
+            io::ErrorKind::CrossesDevices,
+            "can't hardlink across filesystems",
+        ));
+    }
+
+    let tmp = tmp_path(dup);
+    let result = match mode {
+        Mode::Hardlink => fs::hard_link(keeper, &tmp),
+        Mode::Reflink => {
+            reflink(keeper, &tmp).and_then(|_| fs::set_permissions(&tmp, fs::metadata(dup)?.permissions()))
+        }
+        Mode::Symlink => symlink(keeper, &tmp),
+    }
+    .and_then(|_| fs::rename(&tmp, dup));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.map(|_| true)
+}
+
+fn already_linked(keeper: &Path, dup: &Path, mode: Mode) -> io::Result<bool> {
+    match mode {
+        Mode::Hardlink => Ok(device(keeper)? == device(dup)? && inode(keeper)? == inode(dup)?),
+        // reflinks look like any other file
+        Mode::Reflink => Ok(false),
+        Mode::Symlink => Ok(fs::symlink_metadata(dup)?.file_type().is_symlink()),
+    }
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(fs::metadata(path)?.dev())
+}
+
+#[cfg(unix)]
+fn inode(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(fs::metadata(path)?.ino())
+}
+
+#[cfg(unix)]
+fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(not(unix))]
+fn device(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "hardlinks are only supported on unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn inode(path: &Path) -> io::Result<u64> {
+    device(path)
+}
+
+#[cfg(not(unix))]
+fn symlink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "symlinks are only supported on unix",
+    ))
+}
+
+// .name.idup-tmp in the same dir, so the rename stays on one filesystem
+fn tmp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.idup-tmp", name))
+}
+
+// byte by byte, the sha256 in the db might be stale
+pub fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (fa, fb) = (File::open(a)?, File::open(b)?);
+    if fa.metadata()?.len() != fb.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut ra, mut rb) = (BufReader::new(fa), BufReader::new(fb));
+    let (mut ba, mut bb) = ([0u8; 8192], [0u8; 8192]);
+    loop {
+        let n = ra.read(&mut ba)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        rb.read_exact(&mut bb[..n])?;
+        if ba[..n] != bb[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let src = File::open(src)?;
+    let dst_file = File::create_new(dst)?;
+    // SAFETY: both fds are open for the duration of the call
+    if unsafe { libc::ioctl(dst_file.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } != 0 {
+        let err = io::Error::last_os_error();
+        drop(dst_file);
+        let _ = fs::remove_file(dst);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reflinks are only supported on linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks_identical_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        fs::write(&a, b"same").unwrap();
+        fs::write(&b, b"same").unwrap();
+        assert!(replace(&a, &b, Mode::Hardlink).unwrap());
+        assert!(!replace(&a, &b, Mode::Hardlink).unwrap());
+        assert_eq!(fs::read(&b).unwrap(), b"same");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn never_links_a_file_to_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        fs::write(&a, b"same").unwrap();
+        fs::write(&b, b"same").unwrap();
+        assert!(replace(&a, &b, Mode::Symlink).unwrap());
+        assert!(!replace(&b, &a, Mode::Symlink).unwrap());
+        assert_eq!(fs::read(&b).unwrap(), b"same");
+    }
+
+    #[test]
+    fn refuses_different_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        fs::write(&a, b"same").unwrap();
+        fs::write(&b, b"diff").unwrap();
+        assert!(replace(&a, &b, Mode::Symlink).is_err());
+        assert!(!fs::symlink_metadata(&b).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
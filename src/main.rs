@@ -1,11 +1,29 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use env_logger::{Builder, Target};
+use group::Group;
+use image::Rgb;
 use log::{debug, error, info, LevelFilter};
-use std::path::PathBuf;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 mod db;
+mod dedupe;
+mod diff;
+mod fuzzy;
+mod group;
 mod hash;
+mod keypoint;
+mod link;
+mod mih;
+mod montage;
+mod quality;
+mod report;
+mod review;
 mod scan;
+mod script;
+mod trash;
+mod verify;
 
 #[derive(Debug, Parser)]
 #[command(name = "idup", about = "Find duplicate images using avg perceptual hash function")]
@@ -17,18 +35,239 @@ enum Opt {
         #[arg(short, long)]
         recursive: bool,
         // TODO should I add follow symlink opt (it looks to be a nightly feature right now)
+        /// Hex color transparent images are flattened onto before perceptual hashing
+        #[arg(long, default_value = "ffffff", value_parser = hash::parse_rgb)]
+        background: Rgb<u8>,
     },
     /// Retrieve duplicates or near duplicates from the db
     List {
         /// File or folder
         path: Option<PathBuf>,
+        /// List near duplicates using the phash instead of exact matches
+        #[arg(short, long)]
+        fuzzy: bool,
+        /// Max number of differing phash bits for a fuzzy match
+        #[arg(long, default_value_t = 5)]
+        max_dist: u8,
+        /// Max color signature distance (0-510) before two images are considered to differ in color
+        #[arg(long, default_value_t = 64)]
+        color_dist: u32,
+        /// Drop fuzzy matches whose colors differ instead of reporting them
+        #[arg(long)]
+        require_color: bool,
+        /// Load both images of each fuzzy match & compare their pixels (SSIM/PSNR)
+        #[arg(long)]
+        verify: bool,
+        /// Min SSIM (0.0-1.0) for a fuzzy match to be kept when verifying
+        #[arg(long, default_value_t = 0.9)]
+        min_ssim: f64,
+        /// Hex color transparent images are flattened onto before verifying, use the one they were
+        /// scanned w/
+        #[arg(long, default_value = "ffffff", value_parser = hash::parse_rgb)]
+        background: Rgb<u8>,
+        /// Re-rank fuzzy matches by the number of geometrically consistent keypoint matches
+        #[arg(long)]
+        keypoints: bool,
+        /// Min number of consistent keypoint matches for a fuzzy match to be kept
+        #[arg(long, default_value_t = 0, requires = "keypoints")]
+        min_matches: usize,
+        /// Only list groups w/ members under both directories
+        #[arg(long, num_args = 2, value_names = ["DIR_A", "DIR_B"], conflicts_with_all = ["path", "crops"])]
+        between: Option<Vec<PathBuf>>,
+        /// How fuzzy matches are clustered into groups
+        #[arg(long, value_enum, default_value_t = group::Linkage::Single)]
+        linkage: group::Linkage,
+        /// Min fraction (0.0-1.0) of matching frames for two animated images to be listed
+        #[arg(long, default_value_t = 0.8)]
+        min_frame_similarity: f32,
+        /// List images that appear to be crops of other images
+        #[arg(long, conflicts_with = "fuzzy")]
+        crops: bool,
+        /// Filter fuzzy & crop matches on whether the source images had meaningful transparency
+        #[arg(long, value_enum, default_value_t = AlphaFilter::Any)]
+        alpha: AlphaFilter,
+        /// Rules used in order to rank the members of each group, the best is marked as the one to keep
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = quality::DEFAULT_RULES)]
+        rank: Vec<quality::Rule>,
+        /// Print a script w/ a commented out command for every file but the keeper of each group
+        #[arg(long, value_enum, conflicts_with = "crops")]
+        emit_script: Option<script::Shell>,
+        /// Command the script uses for the redundant files
+        #[arg(long, value_enum, default_value_t = script::Action::Rm, requires = "emit_script")]
+        script_action: script::Action,
+    },
+    /// Find the images in the db that look the most like any file, indexed or not
+    Similar {
+        file: PathBuf,
+        /// Number of images to return
+        #[arg(short, long, default_value_t = 10)]
+        top: usize,
+        /// Hex color transparent images are flattened onto before perceptual hashing
+        #[arg(long, default_value = "ffffff", value_parser = hash::parse_rgb)]
+        background: Rgb<u8>,
+    },
+    /// Search the db by a hash value instead of a file
+    #[command(group(clap::ArgGroup::new("hash").required(true)))]
+    Lookup {
+        /// Phash as hex, e.g. 8f8f978589f9f1c0
+        #[arg(long, group = "hash", value_parser = parse_hex_u64)]
+        phash: Option<u64>,
+        /// Max number of differing phash bits
+        #[arg(long, default_value_t = 0, requires = "phash")]
+        max_dist: u8,
+        /// Sha256 of the file or of the decoded image data as hex
+        #[arg(long, group = "hash")]
+        sha256: Option<String>,
+    },
+    /// List images under one dir w/o a duplicate under another, exits w/ 1 if any are missing.
+    /// Images under the first dir that weren't scanned yet are scanned first
+    Missing {
+        /// Dir whose images should all have a copy, e.g. an SD card
+        #[arg(long)]
+        from: PathBuf,
+        /// Dir the copies should be in, e.g. the archive
+        #[arg(long = "in")]
+        in_: PathBuf,
+        /// Also accept near duplicates (phash) as copies
+        #[arg(short, long)]
+        fuzzy: bool,
+        /// Max number of differing phash bits for a near duplicate
+        #[arg(long, default_value_t = 5, requires = "fuzzy")]
+        max_dist: u8,
+        /// Hex color transparent images are flattened onto before perceptual hashing, for images
+        /// under --from that weren't scanned yet
+        #[arg(long, default_value = "ffffff", value_parser = hash::parse_rgb)]
+        background: Rgb<u8>,
+    },
+    /// Delete all but one file of each group of byte for byte identical files. Only prints what would
+    /// be deleted unless --yes is given
+    Dedupe {
+        /// Only groups w/ a member at or under this file or folder
+        path: Option<PathBuf>,
+        /// Which file of each group to keep: oldest, newest, shortest-path or path-priority=<dir>. The
+        /// kept file can be outside path, only the files under path are removed
+        #[arg(long, value_parser = dedupe::parse_keep)]
+        keep: dedupe::Keep,
+        /// Actually delete, quarantine or trash the duplicates
+        #[arg(long)]
+        yes: bool,
+        /// Move duplicates into a quarantine dir next to the db instead of deleting them, see undo
+        #[arg(long)]
+        quarantine: bool,
+        /// Move duplicates into the desktop trash instead of deleting them
+        #[arg(long, conflicts_with = "quarantine")]
+        trash: bool,
+    },
+    /// Replace byte for byte identical copies w/ links to one of them to recover space
+    Link {
+        /// Only groups w/ a member at or under this file or folder
+        path: Option<PathBuf>,
+        #[arg(long, value_enum)]
+        mode: link::Mode,
+        /// Which file of each group the others link to, same policies as dedupe
+        #[arg(long, default_value = "oldest", value_parser = dedupe::parse_keep)]
+        keep: dedupe::Keep,
+        /// Only print what would be linked
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Walk the groups of duplicates in a terminal UI & decide what to keep file by file
+    Review {
+        /// Only groups w/ a member at or under this file or folder
+        path: Option<PathBuf>,
+        /// Review near duplicates using the phash instead of exact matches
+        #[arg(short, long)]
+        fuzzy: bool,
+        /// Max number of differing phash bits for a fuzzy match
+        #[arg(long, default_value_t = 5, requires = "fuzzy")]
+        max_dist: u8,
+        /// How fuzzy matches are clustered into groups
+        #[arg(long, value_enum, default_value_t = group::Linkage::Single)]
+        linkage: group::Linkage,
+        /// Rules used in order to rank the members of each group, the best starts out marked as kept
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = quality::DEFAULT_RULES)]
+        rank: Vec<quality::Rule>,
+        /// How the selected image is previewed
+        #[arg(long, value_enum, default_value_t = review::preview::Protocol::Auto)]
+        preview: review::preview::Protocol,
+    },
+    /// Write the groups of duplicates as HTML pages w/ thumbnails, to share w/ people who don't use a CLI
+    Report {
+        /// Only groups w/ a member at or under this file or folder
+        path: Option<PathBuf>,
+        /// Dir the pages & thumbnails are written to
+        #[arg(long)]
+        html: PathBuf,
+        /// Report near duplicates using the phash instead of exact matches
+        #[arg(short, long)]
+        fuzzy: bool,
+        /// Max number of differing phash bits for a fuzzy match
+        #[arg(long, default_value_t = 5, requires = "fuzzy")]
+        max_dist: u8,
+        /// How fuzzy matches are clustered into groups
+        #[arg(long, value_enum, default_value_t = group::Linkage::Single)]
+        linkage: group::Linkage,
+        /// Rules used in order to rank the members of each group, the best is recommended to keep
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = quality::DEFAULT_RULES)]
+        rank: Vec<quality::Rule>,
+    },
+    /// Put every image of a group into one labeled grid image, e.g. to attach to a ticket
+    Montage {
+        /// Only count groups w/ a member at or under this file or folder, like list
+        path: Option<PathBuf>,
+        /// The group as numbered by list, or list --fuzzy w/ the same --max-dist & --linkage
+        #[arg(long)]
+        group: usize,
+        /// Image to write, the format follows the extension
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Use groups of near duplicates (phash) instead of exact matches
+        #[arg(short, long)]
+        fuzzy: bool,
+        /// Max number of differing phash bits for a fuzzy match
+        #[arg(long, default_value_t = 5, requires = "fuzzy")]
+        max_dist: u8,
+        /// How fuzzy matches are clustered into groups
+        #[arg(long, value_enum, default_value_t = group::Linkage::Single)]
+        linkage: group::Linkage,
+    },
+    /// Remember that images aren't duplicates, so list --fuzzy & review stop matching them
+    #[command(group(clap::ArgGroup::new("images").required(true)))]
+    Ignore {
+        /// Two images that aren't duplicates of each other
+        #[arg(num_args = 2, value_names = ["A", "B"], group = "images")]
+        files: Vec<PathBuf>,
+        /// None of the images in this group are duplicates, numbered as by list --fuzzy w/ the same
+        /// --max-dist & --linkage
+        #[arg(long, group = "images")]
+        group: Option<usize>,
+        /// Max number of differing phash bits for a fuzzy match
+        #[arg(long, default_value_t = 5, requires = "group")]
+        max_dist: u8,
+        /// How fuzzy matches are clustered into groups
+        #[arg(long, value_enum, default_value_t = group::Linkage::Single, requires = "group")]
+        linkage: group::Linkage,
+    },
+    /// Restore files quarantined by dedupe or review & scan them again
+    Undo {
+        /// Only restore this file instead of everything from the last dedupe run
+        #[arg(long)]
+        action_id: Option<i64>,
+        /// Hex color transparent images are flattened onto before perceptual hashing
+        #[arg(long, default_value = "ffffff", value_parser = hash::parse_rgb)]
+        background: Rgb<u8>,
     },
     /// Clean outdated data in the db
     Clean,
     /// Recompute hashes of files in db
     Update,
     /// Print information about a particular file
-    Info { file: PathBuf },
+    Info {
+        file: PathBuf,
+        /// Hex color transparent images are flattened onto before perceptual hashing
+        #[arg(long, default_value = "ffffff", value_parser = hash::parse_rgb)]
+        background: Rgb<u8>,
+    },
     /// Print information about two files
     Compare {
         // TODO should I make this 2..n files?
@@ -37,38 +276,96 @@ enum Opt {
         img1: PathBuf,
         /// File 2
         img2: PathBuf,
+        /// Also compare the pixels of both images (SSIM/PSNR)
+        #[arg(long)]
+        verify: bool,
+        /// Also match keypoints between both images, useful for images w/ overlays or borders
+        #[arg(long)]
+        keypoints: bool,
+        /// Write an image w/ a heatmap of the pixel differences & both phash bit grids
+        #[arg(long)]
+        diff_out: Option<PathBuf>,
+        /// Hex color transparent images are flattened onto before perceptual hashing
+        #[arg(long, default_value = "ffffff", value_parser = hash::parse_rgb)]
+        background: Rgb<u8>,
     },
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AlphaFilter {
+    /// Don't filter on transparency
+    Any,
+    /// Only matches where at least one image had transparency
+    With,
+    /// Only matches where neither image had transparency
+    Without,
+}
+
+impl AlphaFilter {
+    fn keep(&self, a: &db::ImgData, b: &db::ImgData) -> bool {
+        match self {
+            AlphaFilter::Any => true,
+            AlphaFilter::With => a.has_alpha || b.has_alpha,
+            AlphaFilter::Without => !a.has_alpha && !b.has_alpha,
+        }
+    }
+}
+
 fn main() {
+    let opt = Opt::parse();
+    // a script printed by list goes to stdout, so the log can't go there too
+    let target = match opt {
+        Opt::List {
+            emit_script: Some(_), ..
+        } => Target::Stderr,
+        _ => Target::Stdout,
+    };
     Builder::new()
-        .target(Target::Stdout)
+        .target(target)
         .filter_level(LevelFilter::Info)
         .parse_default_env()
         .init();
-    let opt = Opt::parse();
     debug!("{:?}", opt);
 
     match opt {
         // calculate it's phash and print it
-        Opt::Info { file } => {
+        Opt::Info { file, background } => {
             // TODO I need better error handling
-            match hash::phash::hash_path(&file) {
-                Ok(ph) => info!("phash: {:?}", ph),
+            match hash::phash::hash_path(&file, background) {
+                Ok(ph) => info!(
+                    "phash: {:?} hex={:016x}",
+                    ph,
+                    ph.hash.parse::<u64>().unwrap_or_default()
+                ),
                 Err(err) => error!("phash err: {}", err),
             }
             match hash::sha256::hash_path(&file) {
                 Ok(sh) => info!("sha256: {:?}", sh),
                 Err(err) => error!("sha256 err: {}", err),
             }
+            match hash::color::hash_path(&file, background) {
+                Ok(c) => info!("color: {:?}", c),
+                Err(err) => error!("color err: {}", err),
+            }
+            match quality::read(&file) {
+                Ok(meta) => info!("metadata: {}", meta),
+                Err(err) => error!("metadata err: {}", err),
+            }
         }
 
         // calculate both phashes, and dist
-        Opt::Compare { img1, img2 } => {
-            let hash1 = hash::phash::hash_path(&img1).unwrap();
+        Opt::Compare {
+            img1,
+            img2,
+            verify,
+            keypoints,
+            diff_out,
+            background,
+        } => {
+            let hash1 = hash::phash::hash_path(&img1, background).unwrap();
             info!("img1: {:?}", hash1);
 
-            let hash2 = hash::phash::hash_path(&img2).unwrap();
+            let hash2 = hash::phash::hash_path(&img2, background).unwrap();
             info!("img2: {:?}", hash2);
 
             let diff = hash::hamming_dist(hash1, hash2);
@@ -76,33 +373,743 @@ fn main() {
                 Ok(val) => info!("diff: {}", val),
                 Err(_) => error!("failed to calculate dist"),
             }
+
+            if verify {
+                match verify::compare_paths(&img1, &img2, background) {
+                    Ok(score) => info!("ssim: {:.4} psnr: {:.2}", score.ssim, score.psnr),
+                    Err(err) => error!("verify err: {}", err),
+                }
+            }
+
+            if keypoints {
+                match keypoint::compare_paths(&img1, &img2) {
+                    Ok(r) => info!(
+                        "keypoints: img1={} img2={} matches={} consistent={}",
+                        r.keypoints1, r.keypoints2, r.matches, r.consistent
+                    ),
+                    Err(err) => error!("keypoint err: {}", err),
+                }
+            }
+
+            if let Some(out) = diff_out {
+                // flattened the same way as for hashing, so the bit grids match the phashes above
+                let flat = |path: &Path| montage::open(path).map(|img| hash::flatten(img, background));
+                let result = flat(&img1).and_then(|a| flat(&img2).map(|b| diff::render(&a, &b)));
+                match result.and_then(|sheet| sheet.save(&out)) {
+                    Ok(_) => info!("wrote the diff to {:?}", out),
+                    Err(err) => error!("diff err: {}", err),
+                }
+            }
+        }
+
+        // rank every image in the db by its distance to the file
+        Opt::Similar { file, top, background } => {
+            let ph = match hash::phash::hash_path(&file, background) {
+                Ok(ph) => ph,
+                Err(err) => {
+                    error!("phash err: {}", err);
+                    return;
+                }
+            };
+            let target: u64 = ph.hash.parse().unwrap();
+            let mut images = db::fuzzy_candidates().unwrap();
+            // nearest grows its radius until it has enough, build the index for the default --max-dist
+            let index = fuzzy::cached_index(&images, 5);
+            // don't return the file itself if it's already indexed
+            if let Ok(path) = file.canonicalize() {
+                images.retain(|img| img.path != path);
+            }
+            for (img, dist) in fuzzy::nearest(&images, &index, target, top) {
+                info!("{:?} dist={}", img.path, dist);
+            }
+        }
+
+        // find images w/o needing the original file
+        Opt::Lookup {
+            phash,
+            max_dist,
+            sha256,
+        } => {
+            if let Some(phash) = phash {
+                let images = db::fuzzy_candidates().unwrap();
+                let index = fuzzy::cached_index(&images, max_dist);
+                for (img, dist) in fuzzy::within(&images, &index, phash, max_dist) {
+                    info!("{:?} dist={}", img.path, dist);
+                }
+            }
+            if let Some(sha256) = sha256 {
+                for m in db::lookup_sha256(&sha256.to_lowercase()).unwrap() {
+                    info!("{:?} kind={}", m.path, m.kind);
+                }
+            }
+        }
+
+        // images under both dirs are already in dst, anything else needs a copy under dst
+        Opt::Missing {
+            from,
+            in_,
+            fuzzy,
+            max_dist,
+            background,
+        } => {
+            // SAFETY: all paths in the db are absolute
+            let in_ = in_.canonicalize().unwrap();
+            // images that were never scanned or were added since are scanned first, so they can't
+            // pass as copied. the ones that can't be hashed count as missing
+            let indexed: HashSet<PathBuf> = db::sha256_under(&from).unwrap().into_iter().map(|m| m.path).collect();
+            let mut unreadable = Vec::new();
+            for path in scan::images(&from, true) {
+                if indexed.contains(&path) || path.starts_with(&in_) {
+                    continue;
+                }
+                let file_name = path.to_str().unwrap_or("cannot print path due to non-UTF8 chars");
+                if let Err(e) = scan::hash_img(&path, file_name, background) {
+                    error!("Failed to hash {:?}: {}", path, e);
+                    unreadable.push(path);
+                }
+            }
+
+            let dst = db::sha256_under(&in_).unwrap();
+            let dst_hashes: HashSet<&str> = dst.iter().map(|m| m.hash.as_str()).collect();
+            let mut src: Vec<(PathBuf, Vec<String>)> = Vec::new();
+            for m in db::sha256_under(&from).unwrap() {
+                match src.last_mut() {
+                    Some((path, hashes)) if *path == m.path => hashes.push(m.hash),
+                    _ => src.push((m.path, vec![m.hash])),
+                }
+            }
+            src.retain(|(path, hashes)| {
+                !path.starts_with(&in_) && !hashes.iter().any(|h| dst_hashes.contains(h.as_str()))
+            });
+
+            if fuzzy {
+                let images = db::fuzzy_candidates().unwrap();
+                let index = fuzzy::cached_index(&images, max_dist);
+                let phashes: HashMap<PathBuf, u64> = images
+                    .iter()
+                    .filter_map(|img| Some((img.path.clone(), img.phash.parse().ok()?)))
+                    .collect();
+                let (dst_images, _): (Vec<db::ImgData>, _) =
+                    images.into_iter().partition(|img| img.path.starts_with(&in_));
+                src.retain(|(path, _)| match phashes.get(path) {
+                    Some(ph) => fuzzy::within(&dst_images, &index, *ph, max_dist).is_empty(),
+                    None => true,
+                });
+            }
+
+            for (path, _) in &src {
+                info!("{:?}", path);
+            }
+            for path in &unreadable {
+                info!("{:?} (can't be hashed)", path);
+            }
+            info!(
+                "{} image(s) under {:?} missing from {:?}",
+                src.len() + unreadable.len(),
+                from,
+                in_
+            );
+            if !src.is_empty() || !unreadable.is_empty() {
+                std::process::exit(1);
+            }
+        }
+
+        // the keeper of each group is checked to still exist before anything else is deleted
+        Opt::Dedupe {
+            path,
+            keep,
+            yes,
+            quarantine,
+            trash,
+        } => {
+            // SAFETY: all paths in the db are absolute
+            let root = path.as_ref().map(|p| p.canonicalize().unwrap());
+            let batch = db::next_batch().unwrap();
+            let (verb, past) = match (quarantine, trash) {
+                (true, _) => ("quarantine", "quarantined"),
+                (_, true) => ("trash", "trashed"),
+                _ => ("delete", "deleted"),
+            };
+            let (mut done, mut failed) = (0, 0);
+            let mut moves = Vec::new();
+            // rotated, flipped or re-encoded copies are different files, only the same bytes count
+            for group in identical_groups(path.as_deref()) {
+                let paths: Vec<&Path> = group.members.iter().map(|m| m.path.as_path()).collect();
+                let paths = dedupe::distinct_files(&paths);
+                if paths.len() < 2 {
+                    continue;
+                }
+                let keeper = paths[dedupe::keeper(&paths, &keep)];
+                if !keeper.exists() {
+                    error!("group {} skipped, {:?} to keep no longer exists", group.id, keeper);
+                    continue;
+                }
+                info!("group {} keep {:?}", group.id, keeper);
+                // the group only had to have one member under root, the others are left alone
+                let under_root = |p: &Path| root.as_ref().is_none_or(|r| p.starts_with(r));
+                for path in paths.iter().filter(|p| **p != keeper && under_root(p)) {
+                    // the db might be stale, so only files that still have the keeper's bytes go
+                    match link::same_contents(keeper, path) {
+                        Ok(true) => {}
+                        // handled like any other file that's already gone below
+                        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                        Ok(false) => {
+                            error!("  {:?} skipped, it changed since it was scanned", path);
+                            failed += 1;
+                            continue;
+                        }
+                        Err(err) => {
+                            error!("  {:?} skipped, can't compare it to {:?}: {}", path, keeper, err);
+                            failed += 1;
+                            continue;
+                        }
+                    }
+                    let dst = dedupe::quarantine_path(&db::quarantine_dir(), batch, path);
+                    if !yes {
+                        if quarantine {
+                            info!("  would quarantine {:?} to {:?}", path, dst);
+                        } else {
+                            info!("  would {} {:?}", verb, path);
+                        }
+                        continue;
+                    }
+                    let result = if quarantine {
+                        dedupe::move_file(path, &dst).map(|_| dst)
+                    } else if trash {
+                        trash::trash(path)
+                    } else {
+                        std::fs::remove_file(path).map(|_| PathBuf::new())
+                    };
+                    match result {
+                        // recorded & removed from the db together w/ the other moves below
+                        Ok(dst) if quarantine => {
+                            done += 1;
+                            info!("  quarantined {:?} to {:?}", path, dst);
+                            moves.push((path.to_path_buf(), dst));
+                            continue;
+                        }
+                        Ok(dst) if trash => {
+                            done += 1;
+                            info!("  trashed {:?} to {:?}", path, dst);
+                        }
+                        Ok(_) => {
+                            done += 1;
+                            info!("  deleted {:?}", path);
+                        }
+                        // already gone, so the db just needs to catch up
+                        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                            info!("  {:?} was already deleted", path)
+                        }
+                        Err(err) => {
+                            error!("  Failed to {} {:?}: {}", verb, path, err);
+                            failed += 1;
+                            continue;
+                        }
+                    }
+                    if let Err(e) = db::remove(path) {
+                        error!("Failed to remove {:?} from the db: {}", path, e);
+                    }
+                }
+            }
+            // if the moves can't be recorded undo couldn't restore them, so they're moved back
+            let saved = if moves.is_empty() {
+                Ok(())
+            } else {
+                db::save_review(batch, &moves, &[])
+            };
+            if let Err(e) = saved {
+                error!("Failed to record the quarantined files: {}, rolling back", e);
+                for (src, dst) in moves.iter().rev() {
+                    match dedupe::move_file(dst, src) {
+                        Ok(_) => info!("moved {:?} back", src),
+                        Err(e) => error!("Failed to move {:?} back from {:?}: {}", src, dst, e),
+                    }
+                }
+                std::process::exit(1);
+            }
+            if yes {
+                info!("{} {} file(s), {} failed", past, done, failed);
+            } else {
+                info!("nothing was changed, pass --yes to {} the files above", verb);
+            }
+        }
+
+        // files marked for deletion are quarantined as one batch, so undo can bring them back
+        Opt::Review {
+            path,
+            fuzzy,
+            max_dist,
+            linkage,
+            rank,
+            preview,
+        } => {
+            let images = db::fuzzy_candidates().unwrap();
+            let groups = duplicate_groups(&images, path.as_deref(), fuzzy, max_dist, linkage);
+            if groups.is_empty() {
+                info!("no duplicates to review");
+                return;
+            }
+            let meta = db::metadata().unwrap();
+            let keepers: Vec<usize> = groups
+                .iter()
+                .map(|g| {
+                    let paths: Vec<&Path> = g.members.iter().map(|m| m.path.as_path()).collect();
+                    let ranks = quality::rank(&paths, &meta, &rank);
+                    ranks.iter().position(|r| *r == 1).unwrap_or(0)
+                })
+                .collect();
+            let decisions = match review::run(&groups, &keepers, &meta, preview) {
+                Ok(Some(decisions)) => decisions,
+                Ok(None) => {
+                    info!("quit w/o applying anything");
+                    return;
+                }
+                Err(err) => {
+                    error!("review err: {}", err);
+                    return;
+                }
+            };
+            let hashes = imgdata_hashes(&images);
+            let mut not_duplicates = Vec::new();
+            for (group, decisions) in groups.iter().zip(&decisions) {
+                // a file that isn't a duplicate isn't one of any other member
+                let members = &group.members;
+                for i in 0..members.len() {
+                    for j in i + 1..members.len() {
+                        if decisions[i] != review::Decision::NotDuplicate
+                            && decisions[j] != review::Decision::NotDuplicate
+                        {
+                            continue;
+                        }
+                        if let Some(pair) = not_duplicate_pair(&hashes, &members[i].path, &members[j].path) {
+                            not_duplicates.push(pair);
+                        }
+                    }
+                }
+            }
+
+            // all or nothing, if a file can't be moved or the db can't be updated the files moved so
+            // far are moved back
+            let batch = db::next_batch().unwrap();
+            let mut moves = Vec::new();
+            let mut result = Ok(());
+            let deleted = groups
+                .iter()
+                .zip(&decisions)
+                .flat_map(|(g, d)| g.members.iter().zip(d))
+                .filter(|(_, d)| **d == review::Decision::Delete);
+            for (member, _) in deleted {
+                let dst = dedupe::quarantine_path(&db::quarantine_dir(), batch, &member.path);
+                if let Err(err) = dedupe::move_file(&member.path, &dst) {
+                    result = Err(format!("Failed to quarantine {:?}: {}", member.path, err));
+                    break;
+                }
+                info!("quarantined {:?} to {:?}", member.path, dst);
+                moves.push((member.path.clone(), dst));
+            }
+            let result = result.and_then(|_| {
+                db::save_review(batch, &moves, &not_duplicates)
+                    .map_err(|e| format!("Failed to record the decisions: {}", e))
+            });
+            if let Err(err) = result {
+                error!("{}, rolling back", err);
+                for (src, dst) in moves.iter().rev() {
+                    match dedupe::move_file(dst, src) {
+                        Ok(_) => info!("moved {:?} back", src),
+                        Err(e) => error!("Failed to move {:?} back from {:?}: {}", src, dst, e),
+                    }
+                }
+                std::process::exit(1);
+            }
+            info!(
+                "quarantined {} file(s), see undo. {} pair(s) marked as not duplicates",
+                moves.len(),
+                not_duplicates.len()
+            );
+        }
+
+        Opt::Report {
+            path,
+            html,
+            fuzzy,
+            max_dist,
+            linkage,
+            rank,
+        } => {
+            let images = db::fuzzy_candidates().unwrap();
+            let groups = duplicate_groups(&images, path.as_deref(), fuzzy, max_dist, linkage);
+            let meta = db::metadata().unwrap();
+            match report::write(&html, &groups, &meta, &rank) {
+                Ok(_) => info!("wrote {} group(s) to {:?}", groups.len(), html.join("index.html")),
+                Err(err) => error!("Failed to write the report to {:?}: {}", html, err),
+            }
+        }
+
+        // tiles are numbered in the order of the group, the paths are printed w/ their number
+        Opt::Montage {
+            path,
+            group,
+            output,
+            fuzzy,
+            max_dist,
+            linkage,
+        } => {
+            let images = db::fuzzy_candidates().unwrap();
+            let groups = duplicate_groups(&images, path.as_deref(), fuzzy, max_dist, linkage);
+            let Some(group) = groups.into_iter().find(|g| g.id == group) else {
+                error!("There is no group {}", group);
+                return;
+            };
+            let mut tiles = Vec::new();
+            let mut labels = Vec::new();
+            for (i, member) in group.members.iter().enumerate() {
+                info!("#{} {:?}", i + 1, member.path);
+                let img = montage::open(&member.path);
+                let size = match &img {
+                    Ok(img) => format!("{}x{}", img.width(), img.height()),
+                    Err(err) => {
+                        error!("Failed to open {:?}: {}", member.path, err);
+                        "unreadable".to_string()
+                    }
+                };
+                let relation = match (&member.transformation, member.dist) {
+                    (Some(t), _) if t == "none" => "exact".to_string(),
+                    (Some(t), _) => format!("exact {}", t),
+                    _ if i == 0 => "reference".to_string(),
+                    (None, Some(dist)) => format!("dist={}", dist),
+                    (None, None) => "linked".to_string(),
+                };
+                tiles.push(img.ok());
+                labels.push(vec![format!("#{} {}", i + 1, size), relation]);
+            }
+            match montage::sheet(&tiles, &labels).save(&output) {
+                Ok(_) => info!("wrote group {} to {:?}", group.id, output),
+                Err(err) => error!("Failed to write {:?}: {}", output, err),
+            }
+        }
+
+        // keyed on the imgdata hashes, so the decision survives moving the files
+        Opt::Ignore {
+            files,
+            group,
+            max_dist,
+            linkage,
+        } => {
+            let images = db::fuzzy_candidates().unwrap();
+            let paths: Vec<PathBuf> = match group {
+                Some(id) => match near_groups(&images, max_dist, linkage).into_iter().find(|g| g.id == id) {
+                    Some(g) => g.members.into_iter().map(|m| m.path).collect(),
+                    None => {
+                        error!("There is no group {}", id);
+                        return;
+                    }
+                },
+                // SAFETY: all paths in the db are absolute
+                None => files.iter().map(|f| f.canonicalize().unwrap()).collect(),
+            };
+            let hashes = imgdata_hashes(&images);
+            let mut pairs = Vec::new();
+            for (i, a) in paths.iter().enumerate() {
+                for b in &paths[i + 1..] {
+                    if let Some(pair) = not_duplicate_pair(&hashes, a, b) {
+                        pairs.push(pair);
+                    }
+                }
+            }
+            db::save_not_duplicates(&pairs).unwrap();
+            info!("{} pair(s) marked as not duplicates", pairs.len());
+        }
+
+        // the contents are the same, so the db doesn't need to change
+        Opt::Link {
+            path,
+            mode,
+            keep,
+            dry_run,
+        } => {
+            let (mut linked, mut failed) = (0, 0);
+            for group in identical_groups(path.as_deref()) {
+                let paths: Vec<&Path> = group.members.iter().map(|m| m.path.as_path()).collect();
+                let paths = dedupe::distinct_files(&paths);
+                if paths.len() < 2 {
+                    continue;
+                }
+                let keeper = paths[dedupe::keeper(&paths, &keep)];
+                info!("group {} keep {:?}", group.id, keeper);
+                for path in paths.iter().filter(|p| **p != keeper) {
+                    if dry_run {
+                        info!("  would link {:?}", path);
+                        continue;
+                    }
+                    match link::replace(keeper, path, mode) {
+                        Ok(true) => {
+                            info!("  linked {:?}", path);
+                            linked += 1;
+                        }
+                        Ok(false) => info!("  {:?} is already linked", path),
+                        Err(err) => {
+                            error!("  Failed to link {:?}: {}", path, err);
+                            failed += 1;
+                        }
+                    }
+                }
+            }
+            if !dry_run {
+                info!("linked {} file(s), {} failed", linked, failed);
+            }
+        }
+
+        // files are only restored if nothing took their place in the meantime
+        Opt::Undo { action_id, background } => {
+            let actions = db::pending_actions(action_id).unwrap();
+            if actions.is_empty() {
+                info!("Nothing to undo");
+            }
+            for action in actions {
+                if let Err(err) = dedupe::move_file(&action.dst, &action.src) {
+                    error!(
+                        "Failed to restore {:?} from {:?} action={}: {}",
+                        action.src, action.dst, action.action_id, err
+                    );
+                    continue;
+                }
+                info!(
+                    "restored {:?} action={} batch={}",
+                    action.src, action.action_id, action.batch
+                );
+                if let Err(e) = db::mark_undone(action.action_id) {
+                    error!("Failed to mark action={} as undone: {}", action.action_id, e);
+                }
+                scan::process_path(action.src, false, background);
+            }
         }
 
         // Find & store hashes into db
-        Opt::Scan { path, recursive } => {
-            scan::process_path(path, recursive);
+        Opt::Scan {
+            path,
+            recursive,
+            background,
+        } => {
+            scan::process_path(path, recursive, background);
         }
 
         // List matches of file
-        Opt::List { path } => {
-            // TODO future features
-            // - if dir, find all matches that fall under the parent
-            // - if file, find all matches for that file
-            // - if no path given, find all matches in db
-            // - optins to do exact match (sha256) or fuzzy (phash)
-            match path {
-                None => {
-                    let iter = db::exact_matches().unwrap();
-                    for data in iter {
-                        info!("{:?}", data.path);
+        Opt::List {
+            path,
+            crops: true,
+            max_dist,
+            alpha,
+            ..
+        } => {
+            let images = db::fuzzy_candidates().unwrap();
+            let regions = db::crop_regions().unwrap();
+            // SAFETY: all paths in the db are absolute
+            let path = path.map(|p| p.canonicalize().unwrap());
+            for crop in fuzzy::crops(&images, &regions, max_dist) {
+                if let Some(path) = &path {
+                    if !crop.parent.path.starts_with(path) && !crop.child.path.starts_with(path) {
+                        continue;
+                    }
+                }
+                if !alpha.keep(crop.parent, crop.child) {
+                    continue;
+                }
+                let r = crop.region;
+                info!(
+                    "{:?} appears to be a crop of {:?} (region x={}% y={}% w={}% h={}%) dist={}",
+                    crop.child.path, crop.parent.path, r.x, r.y, r.w, r.h, crop.dist
+                );
+            }
+        }
+
+        Opt::List {
+            path,
+            fuzzy: true,
+            max_dist,
+            color_dist,
+            require_color,
+            verify,
+            min_ssim,
+            background,
+            keypoints,
+            min_matches,
+            alpha,
+            min_frame_similarity,
+            linkage,
+            between,
+            rank,
+            emit_script,
+            script_action,
+            ..
+        } => {
+            let script = emit_script.map(|_| script_action);
+            // SAFETY: all paths in the db are absolute
+            let between = between.map(|dirs| canonicalize_pair(&dirs));
+            let images = db::fuzzy_candidates().unwrap();
+            let animated = db::animated().unwrap();
+            // animated images only match on their full frame sequence, reported separately below
+            let animated_paths: HashSet<&PathBuf> = animated.iter().map(|(p, _)| p).collect();
+            // SAFETY: all paths in the db are absolute
+            let path = path.map(|p| p.canonicalize().unwrap());
+            let not_duplicates = db::not_duplicates().unwrap();
+            let mut results = Vec::new();
+            let index = fuzzy::cached_index(&images, max_dist);
+            for pair in fuzzy::pairs(&images, &index, max_dist) {
+                if animated_paths.contains(&pair.a.path) || animated_paths.contains(&pair.b.path) {
+                    continue;
+                }
+                if not_duplicates.contains(&db::pair_key(&pair.a.sha256, &pair.b.sha256)) {
+                    continue;
+                }
+                if !alpha.keep(pair.a, pair.b) {
+                    continue;
+                }
+                let color_differs = pair.color_differs(color_dist);
+                if color_differs && require_color {
+                    continue;
+                }
+                let color = match pair.color_dist {
+                    Some(d) => d.to_string(),
+                    None => "?".to_string(),
+                };
+                let mut score = String::new();
+                if verify {
+                    match verify::compare_paths(&pair.a.path, &pair.b.path, background) {
+                        Ok(s) if s.ssim < min_ssim => {
+                            debug!("discarding {:?} {:?} ssim={:.4}", pair.a.path, pair.b.path, s.ssim);
+                            continue;
+                        }
+                        Ok(s) => score = format!(" ssim={:.4} psnr={:.2}", s.ssim, s.psnr),
+                        Err(err) => {
+                            error!("Failed to verify {:?} {:?}: {}", pair.a.path, pair.b.path, err);
+                            continue;
+                        }
+                    }
+                }
+                let mut consistent = 0;
+                if keypoints {
+                    match keypoint::compare_paths(&pair.a.path, &pair.b.path) {
+                        Ok(r) if r.consistent < min_matches => {
+                            debug!(
+                                "discarding {:?} {:?} matches={}",
+                                pair.a.path, pair.b.path, r.consistent
+                            );
+                            continue;
+                        }
+                        Ok(r) => {
+                            consistent = r.consistent;
+                            score += &format!(" matches={}", r.consistent);
+                        }
+                        Err(err) => {
+                            error!("Failed to match keypoints {:?} {:?}: {}", pair.a.path, pair.b.path, err);
+                            continue;
+                        }
+                    }
+                }
+                let note = if pair.is_exact() {
+                    " (exact)"
+                } else if color_differs {
+                    " (same image, color differs)"
+                } else {
+                    ""
+                };
+                let edge = group::Edge {
+                    a: &pair.a.path,
+                    b: &pair.b.path,
+                    dist: pair.dist,
+                };
+                results.push((edge, consistent, format!(" color_dist={}{}{}", color, score, note)));
+            }
+
+            let edges: Vec<group::Edge> = results.iter().map(|r| r.0).collect();
+            let mut details: HashMap<(&Path, &Path), (usize, &str)> = HashMap::new();
+            for (edge, consistent, detail) in &results {
+                details.insert((edge.a, edge.b), (*consistent, detail));
+                details.insert((edge.b, edge.a), (*consistent, detail));
+            }
+            let apart = apart_pairs(&images, &not_duplicates);
+            let mut groups = group::fuzzy_groups(&edges, linkage, &apart);
+            if let Some(path) = &path {
+                groups.retain(|g| g.members.iter().any(|m| m.path.starts_with(path)));
+            }
+            if let Some((a, b)) = &between {
+                groups.retain(|g| g.spans(a, b));
+            }
+            if keypoints {
+                // groups w/ the most keypoint matches first, ties keep their original order
+                groups.sort_by_key(|g| {
+                    let root = g.members[0].path.as_path();
+                    let best = g.members.iter().filter_map(|m| details.get(&(root, m.path.as_path())));
+                    Reverse(best.map(|d| d.0).max().unwrap_or(0))
+                });
+            }
+            let meta = db::metadata().unwrap();
+            if let Some(action) = script {
+                print!("{}", script::header(action));
+            }
+            for group in &groups {
+                let root = group.members[0].path.as_path();
+                print_group(
+                    group,
+                    &meta,
+                    &rank,
+                    |m| details.get(&(root, m)).map(|d| d.1.to_string()),
+                    script,
+                );
+            }
+
+            for pair in fuzzy::animated_pairs(&animated, max_dist, min_frame_similarity) {
+                if let Some(path) = &path {
+                    if !pair.a.starts_with(path) && !pair.b.starts_with(path) {
+                        continue;
                     }
                 }
-                Some(path) => {
-                    let iter = db::exact_match(&path).unwrap();
-                    for data in iter {
-                        info!("{:?}", data.path);
+                if let Some((a, b)) = &between {
+                    let spans = |x: &Path, y: &Path| pair.a.starts_with(x) && pair.b.starts_with(y);
+                    if !spans(a, b) && !spans(b, a) {
+                        continue;
+                    }
+                }
+                if script.is_some() {
+                    println!(
+                        "\n# animated {} {} similarity={:.2} frames={}/{}",
+                        script::quote(pair.a),
+                        script::quote(pair.b),
+                        pair.similarity,
+                        pair.frames_a,
+                        pair.frames_b
+                    );
+                    continue;
+                }
+                info!(
+                    "animated {:?} {:?} similarity={:.2} frames={}/{}",
+                    pair.a, pair.b, pair.similarity, pair.frames_a, pair.frames_b
+                );
+            }
+        }
+
+        Opt::List {
+            path,
+            between,
+            rank,
+            emit_script,
+            script_action,
+            ..
+        } => {
+            // SAFETY: all paths in the db are absolute
+            let between = between.map(|dirs| canonicalize_pair(&dirs));
+            let meta = db::metadata().unwrap();
+            let script = emit_script.map(|_| script_action);
+            if let Some(action) = script {
+                print!("{}", script::header(action));
+            }
+            for group in exact_groups(path.as_deref()) {
+                if let Some((a, b)) = &between {
+                    if !group.spans(a, b) {
+                        continue;
                     }
                 }
+                print_group(&group, &meta, &rank, |_| None, script);
             }
         }
 
@@ -111,3 +1118,176 @@ fn main() {
         }
     }
 }
+
+// if a file or dir is given, only groups w/ a member at or under that path
+fn exact_groups(path: Option<&Path>) -> Vec<Group> {
+    // the first frame of animated images doesn't make them the same image, they only match on
+    // their file bytes here & on their full frame sequence in list --fuzzy
+    let animated: HashSet<PathBuf> = db::animated().unwrap().into_iter().map(|(p, _)| p).collect();
+    let mut matches = match path {
+        None => db::exact_matches().unwrap(),
+        Some(path) => db::exact_match(path).unwrap(),
+    };
+    matches.retain(|m| m.kind == "sha256 sha256" || !animated.contains(&m.path));
+    group::exact_groups(&matches)
+}
+
+// groups of near duplicates like list --fuzzy w/o its optional filters, animated images & pairs
+// marked as not duplicates are left out
+fn near_groups(images: &[db::ImgData], max_dist: u8, linkage: group::Linkage) -> Vec<Group> {
+    let animated: HashSet<PathBuf> = db::animated().unwrap().into_iter().map(|(p, _)| p).collect();
+    let not_duplicates = db::not_duplicates().unwrap();
+    let apart = apart_pairs(images, &not_duplicates);
+    let index = fuzzy::cached_index(images, max_dist);
+    let edges: Vec<group::Edge> = fuzzy::pairs(images, &index, max_dist)
+        .iter()
+        .filter(|p| !animated.contains(&p.a.path) && !animated.contains(&p.b.path))
+        .filter(|p| !not_duplicates.contains(&db::pair_key(&p.a.sha256, &p.b.sha256)))
+        .map(|p| group::Edge {
+            a: &p.a.path,
+            b: &p.b.path,
+            dist: p.dist,
+        })
+        .collect();
+    group::fuzzy_groups(&edges, linkage, &apart)
+}
+
+// the paths of every pair of images marked as not duplicates, so they aren't grouped through others
+fn apart_pairs<'a>(images: &'a [db::ImgData], not_duplicates: &HashSet<(String, String)>) -> Vec<(&'a Path, &'a Path)> {
+    let mut by_hash: HashMap<&str, Vec<&Path>> = HashMap::new();
+    for img in images.iter().filter(|img| !img.sha256.is_empty()) {
+        by_hash.entry(&img.sha256).or_default().push(&img.path);
+    }
+    let mut pairs = Vec::new();
+    for (a, b) in not_duplicates {
+        if let (Some(pa), Some(pb)) = (by_hash.get(a.as_str()), by_hash.get(b.as_str())) {
+            pairs.extend(pa.iter().flat_map(|a| pb.iter().map(move |b| (*a, *b))));
+        }
+    }
+    pairs
+}
+
+// exact groups or near duplicate groups (see near_groups), only those w/ a member at or under path if given
+fn duplicate_groups(
+    images: &[db::ImgData],
+    path: Option<&Path>,
+    fuzzy: bool,
+    max_dist: u8,
+    linkage: group::Linkage,
+) -> Vec<Group> {
+    if !fuzzy {
+        return exact_groups(path);
+    }
+    let mut groups = near_groups(images, max_dist, linkage);
+    // SAFETY: all paths in the db are absolute
+    if let Some(path) = path.map(|p| p.canonicalize().unwrap()) {
+        groups.retain(|g| g.members.iter().any(|m| m.path.starts_with(&path)));
+    }
+    groups
+}
+
+fn imgdata_hashes(images: &[db::ImgData]) -> HashMap<&Path, &str> {
+    images
+        .iter()
+        .filter(|img| !img.sha256.is_empty())
+        .map(|img| (img.path.as_path(), img.sha256.as_str()))
+        .collect()
+}
+
+// the imgdata hashes of two images to mark as not duplicates, None if that's impossible
+fn not_duplicate_pair<'a>(hashes: &HashMap<&Path, &'a str>, a: &Path, b: &Path) -> Option<(&'a str, &'a str)> {
+    match (hashes.get(a), hashes.get(b)) {
+        (Some(ha), Some(hb)) if ha == hb => {
+            error!(
+                "{:?} & {:?} have the same pixels, they can't be marked as not duplicates",
+                a, b
+            );
+            None
+        }
+        (Some(ha), Some(hb)) => Some((ha, hb)),
+        _ => {
+            error!("{:?} or {:?} has no imgdata hash in the db, scan it first", a, b);
+            None
+        }
+    }
+}
+
+// groups of files w/ the same bytes, unlike exact_groups which also matches the same pixels
+fn identical_groups(path: Option<&Path>) -> Vec<Group> {
+    let mut matches = match path {
+        None => db::exact_matches().unwrap(),
+        Some(path) => db::exact_match(path).unwrap(),
+    };
+    matches.retain(|m| m.kind == "sha256 sha256");
+    group::exact_groups(&matches)
+}
+
+fn canonicalize_pair(dirs: &[PathBuf]) -> (PathBuf, PathBuf) {
+    (dirs[0].canonicalize().unwrap(), dirs[1].canonicalize().unwrap())
+}
+
+fn parse_hex_u64(s: &str) -> Result<u64, String> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|e| e.to_string())
+}
+
+// the rank & how each member matched the group's first member, detail gives extra info to print
+// for each member besides the first
+fn describe_group(
+    group: &Group,
+    meta: &HashMap<PathBuf, quality::Metadata>,
+    rules: &[quality::Rule],
+    detail: impl Fn(&Path) -> Option<String>,
+) -> (Vec<String>, Vec<usize>) {
+    let paths: Vec<&Path> = group.members.iter().map(|m| m.path.as_path()).collect();
+    let ranks = quality::rank(&paths, meta, rules);
+    let notes = group
+        .members
+        .iter()
+        .enumerate()
+        .map(|(i, member)| {
+            let mut note = String::new();
+            if let Some(t) = &member.transformation {
+                note += &format!(" transformation={}", t);
+            }
+            if i > 0 && member.transformation.is_none() {
+                match (member.dist, detail(&member.path)) {
+                    (_, Some(d)) => note += &format!(" dist={}{}", member.dist.unwrap_or_default(), d),
+                    (Some(dist), None) => note += &format!(" dist={}", dist),
+                    (None, None) => note += " (linked through other members)",
+                }
+            }
+            note += &format!(" rank={}", ranks[i]);
+            if let Some(m) = meta.get(&member.path) {
+                note += &format!(" {}", m);
+            }
+            note
+        })
+        .collect();
+    (notes, ranks)
+}
+
+// members are ranked by the rules & the best one is marked as the one to keep
+fn print_group(
+    group: &Group,
+    meta: &HashMap<PathBuf, quality::Metadata>,
+    rules: &[quality::Rule],
+    detail: impl Fn(&Path) -> Option<String>,
+    script: Option<script::Action>,
+) {
+    let (notes, ranks) = describe_group(group, meta, rules, detail);
+    if let Some(action) = script {
+        let paths: Vec<&Path> = group.members.iter().map(|m| m.path.as_path()).collect();
+        let keeper = ranks.iter().position(|r| *r == 1).unwrap_or(0);
+        // the script goes to stdout as is, w/o the log prefix
+        print!(
+            "{}",
+            script::group(group.id, &group.kind, &paths, &notes, keeper, action)
+        );
+        return;
+    }
+    info!("group {} kind={} members={}", group.id, group.kind, group.members.len());
+    for (i, member) in group.members.iter().enumerate() {
+        let keep = if ranks[i] == 1 { " (keep)" } else { "" };
+        info!("  {:?}{}{}", member.path, notes[i], keep);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::hash::hamming_dist_u64;
+use std::collections::HashMap;
+use std::fs::{rename, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// identifies the on disk format, bump the version if it changes
+const MAGIC: &[u8; 8] = b"IDUPMI02";
+
+// chunks narrower than this match too many hashes each to be worth looking up
+const MAX_CHUNKS: u8 = 16;
+
+// multi-index hashing over 64 bit hashes. each hash is split into chunks & every chunk is indexed
+// by its exact value. two hashes within r bits of each other split into r + 1 chunks must share at
+// least one chunk exactly (pigeonhole), so a search only checks the full distance of the hashes
+// that share a chunk w/ the query instead of every hash
+#[derive(Debug)]
+pub struct MultiIndex {
+    // (shift, width) of each chunk
+    chunks: Vec<(u32, u32)>,
+    entries: Vec<Entry>,
+    // hash -> index into entries
+    by_hash: HashMap<u64, usize>,
+    // for each chunk: chunk value -> (hash, index into entries) of every entry w/ that chunk. the
+    // hash is kept next to the index so checking a candidate doesn't have to look up its entry
+    tables: Vec<HashMap<u64, Vec<(u64, usize)>>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    hash: u64,
+    // ids of every item w/ this exact hash
+    ids: Vec<usize>,
+}
+
+impl MultiIndex {
+    // an index that only has to look up exact chunk matches for searches up to max_dist
+    pub fn new(max_dist: u8) -> MultiIndex {
+        let count = max_dist.saturating_add(1).min(MAX_CHUNKS) as u32;
+        let chunks = (0..count)
+            .map(|i| {
+                let (start, end) = (i * 64 / count, (i + 1) * 64 / count);
+                (start, end - start)
+            })
+            .collect();
+        MultiIndex {
+            chunks,
+            entries: Vec::new(),
+            by_hash: HashMap::new(),
+            tables: (0..count).map(|_| HashMap::new()).collect(),
+        }
+    }
+
+    pub fn insert(&mut self, hash: u64, id: usize) {
+        if let Some(&i) = self.by_hash.get(&hash) {
+            self.entries[i].ids.push(id);
+            return;
+        }
+        let i = self.entries.len();
+        self.entries.push(Entry { hash, ids: vec![id] });
+        self.by_hash.insert(hash, i);
+        for (c, table) in self.tables.iter_mut().enumerate() {
+            table.entry(chunk(hash, self.chunks[c])).or_default().push((hash, i));
+        }
+    }
+
+    // every (id, dist) within max_dist of hash, in no particular order. only looks at the hashes
+    // that share a chunk w/ hash, or that are within max_dist / chunks bits of one when searching
+    // further than the index was built for
+    pub fn find(&self, hash: u64, max_dist: u8) -> Vec<(usize, u8)> {
+        let mut results = Vec::new();
+        let flips = max_dist as u32 / self.chunks.len() as u32;
+        let lookups: u64 = self.chunks.iter().map(|(_, width)| variants(*width, flips)).sum();
+        // looking up that many chunk values costs more than checking everything
+        if lookups >= self.entries.len() as u64 {
+            for entry in &self.entries {
+                let dist = hamming_dist_u64(hash, entry.hash);
+                if dist <= max_dist {
+                    results.extend(entry.ids.iter().map(|id| (*id, dist)));
+                }
+            }
+            return results;
+        }
+        for (c, table) in self.tables.iter().enumerate() {
+            let (_, width) = self.chunks[c];
+            each_within(chunk(hash, self.chunks[c]), width, flips, &mut |value| {
+                for &(other, i) in table.get(&value).into_iter().flatten() {
+                    let dist = hamming_dist_u64(hash, other);
+                    if dist > max_dist {
+                        continue;
+                    }
+                    // already found when looking up an earlier chunk
+                    let found = self.chunks[..c]
+                        .iter()
+                        .any(|ch| hamming_dist_u64(chunk(hash, *ch), chunk(other, *ch)) as u32 <= flips);
+                    if !found {
+                        results.extend(self.entries[i].ids.iter().map(|id| (*id, dist)));
+                    }
+                }
+            });
+        }
+        results
+    }
+
+    // the k closest (id, dist), closest first. grows the search radius until enough are found
+    pub fn nearest(&self, hash: u64, k: usize) -> Vec<(usize, u8)> {
+        let mut radius = 0;
+        loop {
+            let mut results = self.find(hash, radius);
+            if results.len() >= k || radius >= 64 {
+                results.sort_by_key(|(id, dist)| (*dist, *id));
+                results.truncate(k);
+                return results;
+            }
+            radius = (radius * 2).clamp(1, 64);
+        }
+    }
+
+    // every (id, hash) in the index
+    pub fn entries(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry.ids.iter().map(move |id| (*id, entry.hash)))
+    }
+}
+
+fn chunk(hash: u64, (shift, width): (u32, u32)) -> u64 {
+    (hash >> shift) & ((1u64 << width) - 1)
+}
+
+// how many values are within flips bits of a width bit value
+fn variants(width: u32, flips: u32) -> u64 {
+    let mut total = 0u64;
+    let mut n_choose_k = 1u64;
+    for k in 0..=flips.min(width) {
+        total = total.saturating_add(n_choose_k);
+        n_choose_k = n_choose_k.saturating_mul((width - k) as u64) / (k as u64 + 1);
+    }
+    total
+}
+
+// calls f w/ every width bit value within flips bits of value, including value itself
+fn each_within(value: u64, width: u32, flips: u32, f: &mut impl FnMut(u64)) {
+    flip_from(value, 0, width, flips, f);
+}
+
+// only flips bits above the last one flipped, so every value is visited once
+fn flip_from(value: u64, from: u32, width: u32, flips: u32, f: &mut impl FnMut(u64)) {
+    f(value);
+    if flips > 0 {
+        for bit in from..width {
+            flip_from(value ^ (1 << bit), bit + 1, width, flips - 1, f);
+        }
+    }
+}
+
+// on disk cache, all numbers are little endian:
+// magic, db id u64, generation u64, entry count u64, then for each entry: hash u64, id count u32, ids u64...
+// the chunk tables aren't saved, they're rebuilt on load for the max_dist being searched
+impl MultiIndex {
+    // writes to a temp file first so a crash never leaves a half written cache behind, the temp
+    // file is named per process so two of them saving at once don't write to the same one
+    pub fn save(&self, path: &Path, (id, generation): (u64, u64)) -> io::Result<()> {
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        w.write_all(MAGIC)?;
+        w.write_all(&id.to_le_bytes())?;
+        w.write_all(&generation.to_le_bytes())?;
+        w.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for entry in &self.entries {
+            w.write_all(&entry.hash.to_le_bytes())?;
+            w.write_all(&(entry.ids.len() as u32).to_le_bytes())?;
+            for id in &entry.ids {
+                w.write_all(&(*id as u64).to_le_bytes())?;
+            }
+        }
+        w.into_inner()?.sync_all()?;
+        rename(tmp, path)
+    }
+
+    // returns the (db id, generation) the index was saved w/ along w/ the index
+    pub fn load(path: &Path, max_dist: u8) -> io::Result<((u64, u64), MultiIndex)> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an index cache"));
+        }
+        let id = read_u64(&mut r)?;
+        let generation = read_u64(&mut r)?;
+        let count = read_u64(&mut r)?;
+        let mut index = MultiIndex::new(max_dist);
+        for _ in 0..count {
+            let hash = read_u64(&mut r)?;
+            for _ in 0..read_u32(&mut r)? {
+                index.insert(hash, read_u64(&mut r)? as usize);
+            }
+        }
+        Ok(((id, generation), index))
+    }
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn index(hashes: &[u64], max_dist: u8) -> MultiIndex {
+        let mut index = MultiIndex::new(max_dist);
+        for (id, hash) in hashes.iter().enumerate() {
+            index.insert(*hash, id);
+        }
+        index
+    }
+
+    // splitmix64, good enough to spread hashes over the whole 64 bits
+    fn random_hashes(n: usize) -> Vec<u64> {
+        let mut state = 0x9e3779b97f4a7c15u64;
+        (0..n)
+            .map(|_| {
+                state = state.wrapping_add(0x9e3779b97f4a7c15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                z ^ (z >> 31)
+            })
+            .collect()
+    }
+
+    fn brute_force(hashes: &[u64], target: u64, max_dist: u8) -> Vec<usize> {
+        (0..hashes.len())
+            .filter(|i| hamming_dist_u64(hashes[*i], target) <= max_dist)
+            .collect()
+    }
+
+    #[test]
+    fn find_matches_brute_force() {
+        let mut hashes = random_hashes(500);
+        // a few near copies of one hash so there is something to find
+        for bits in [0b1, 0b1011, 0xff, 0xffff_0000_0000] {
+            hashes.push(hashes[42] ^ bits);
+        }
+        // the index is built for 3, but has to find matches at any distance
+        let index = index(&hashes, 3);
+        for max_dist in [0, 3, 5, 7, 10, 30] {
+            let target = hashes[42] ^ 0b1000_0000;
+            let mut found: Vec<usize> = index.find(target, max_dist).into_iter().map(|(id, _)| id).collect();
+            found.sort();
+            assert_eq!(found, brute_force(&hashes, target, max_dist));
+        }
+    }
+
+    #[test]
+    fn duplicates_share_an_entry() {
+        let index = index(&[7, 7, 8], 2);
+        let mut found = index.find(7, 0);
+        found.sort();
+        assert_eq!(found, vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn within_counts_every_value_once() {
+        let mut seen = Vec::new();
+        each_within(0b0101, 4, 2, &mut |v| seen.push(v));
+        assert_eq!(seen.len() as u64, variants(4, 2));
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len() as u64, variants(4, 2));
+        assert!(seen.iter().all(|v| (v ^ 0b0101).count_ones() <= 2));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let hashes = random_hashes(100);
+        let index = index(&hashes, 5);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("idup.mih");
+        index.save(&path, (3, 7)).unwrap();
+        let (generation, loaded) = MultiIndex::load(&path, 5).unwrap();
+        assert_eq!(generation, (3, 7));
+        assert_eq!(
+            loaded.entries().collect::<Vec<_>>(),
+            index.entries().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.find(hashes[3], 5), index.find(hashes[3], 5));
+    }
+
+    #[test]
+    fn nearest_is_sorted() {
+        let index = index(&[0b1111, 0b0001, 0b0111, 0xffff_0000], 5);
+        assert_eq!(index.nearest(0, 2), vec![(1, 1), (2, 3)]);
+    }
+
+    // cargo test --release -- --ignored --nocapture all_pairs_speed_up
+    #[test]
+    #[ignore]
+    fn all_pairs_speed_up() {
+        let n = 500_000;
+        let max_dist = 5;
+        let hashes = random_hashes(n);
+
+        let start = Instant::now();
+        let index = index(&hashes, max_dist);
+        let built = start.elapsed();
+        let mut found = 0;
+        for hash in &hashes {
+            found += index.find(*hash, max_dist).len();
+        }
+        let indexed = start.elapsed();
+
+        // brute force over every hash would take minutes, time a sample of the queries instead
+        let sample = 2_000;
+        let start = Instant::now();
+        let mut brute = 0;
+        for hash in &hashes[..sample] {
+            brute += brute_force(&hashes, *hash, max_dist).len();
+        }
+        let per_query = start.elapsed() / sample as u32;
+        let scan = per_query * n as u32;
+
+        println!(
+            "{} hashes at max_dist={}: index built in {:?}, all pairs in {:?} ({} matches). brute force {:?} per query, ~{:?} for all pairs ({} matches in the sample)",
+            n, max_dist, built, indexed, found, per_query, scan, brute
+        );
+        assert!(indexed * 10 < scan);
+    }
+}
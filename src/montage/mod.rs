@@ -0,0 +1,78 @@
+use font::{draw_text, GLYPH_HEIGHT, GLYPH_WIDTH};
+use image::imageops::overlay;
+use image::{DynamicImage, ImageReader, ImageResult, Rgb, RgbImage, Rgba, RgbaImage};
+use std::path::Path;
+
+pub mod font;
+
+// max width & height of an image on the sheet, smaller images aren't scaled up
+const TILE: u32 = 256;
+const PAD: u32 = 8;
+// every font pixel is drawn as SCALE x SCALE pixels
+const SCALE: u32 = 2;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT * SCALE + 4;
+
+pub fn open(path: &Path) -> ImageResult<DynamicImage> {
+    ImageReader::open(path)?.with_guessed_format()?.decode()
+}
+
+// the images in a grid that's about as wide as it's tall, w/ the lines of each label below its
+// image. unreadable images (None) leave their tile empty
+pub fn sheet(images: &[Option<DynamicImage>], labels: &[Vec<String>]) -> RgbImage {
+    let cols = (images.len() as f64).sqrt().ceil().max(1.0) as u32;
+    let rows = (images.len() as u32).div_ceil(cols).max(1);
+    let lines = labels.iter().map(|l| l.len()).max().unwrap_or(0) as u32;
+    let (cell_w, cell_h) = (TILE + 2 * PAD, TILE + 2 * PAD + lines * LINE_HEIGHT);
+    let mut sheet = RgbaImage::from_pixel(cols * cell_w, rows * cell_h, Rgba([255, 255, 255, 255]));
+    let corner = |i: usize| ((i as u32 % cols) * cell_w + PAD, (i as u32 / cols) * cell_h + PAD);
+    for (i, img) in images.iter().enumerate() {
+        let (x, y) = corner(i);
+        // a light background shows the tile of small or transparent images
+        overlay(
+            &mut sheet,
+            &RgbaImage::from_pixel(TILE, TILE, Rgba([238, 238, 238, 255])),
+            x as i64,
+            y as i64,
+        );
+        let Some(img) = img else { continue };
+        let img = if img.width() > TILE || img.height() > TILE {
+            img.thumbnail(TILE, TILE)
+        } else {
+            img.clone()
+        };
+        let (dx, dy) = ((TILE - img.width()) / 2, (TILE - img.height()) / 2);
+        overlay(&mut sheet, &img.to_rgba8(), (x + dx) as i64, (y + dy) as i64);
+    }
+
+    let mut sheet = DynamicImage::ImageRgba8(sheet).into_rgb8();
+    let max_chars = (TILE / ((GLYPH_WIDTH + 1) * SCALE)) as usize;
+    for (i, label) in labels.iter().enumerate() {
+        let (x, y) = corner(i);
+        for (j, line) in label.iter().enumerate() {
+            let line: String = line.chars().take(max_chars).collect();
+            let top = y + TILE + PAD + j as u32 * LINE_HEIGHT;
+            draw_text(&mut sheet, x, top, &line, SCALE, Rgb([0, 0, 0]));
+        }
+    }
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_a_square_grid() {
+        let img = DynamicImage::new_rgb8(10, 10);
+        let images = vec![Some(img.clone()), None, Some(img)];
+        let labels = vec![vec!["#1".to_string(), "dist=0".to_string()]; 3];
+        let sheet = sheet(&images, &labels);
+        let (cell_w, cell_h) = (TILE + 2 * PAD, TILE + 2 * PAD + 2 * LINE_HEIGHT);
+        assert_eq!(sheet.dimensions(), (2 * cell_w, 2 * cell_h));
+        // small images are centered in their tile, not scaled up
+        let center = PAD + TILE / 2;
+        assert_eq!(*sheet.get_pixel(center, center), Rgb([0, 0, 0]));
+        assert_eq!(*sheet.get_pixel(PAD + 1, PAD + 1), Rgb([238, 238, 238]));
+        assert_eq!(*sheet.get_pixel(cell_w + center, center), Rgb([238, 238, 238]));
+    }
+}
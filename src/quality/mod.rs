@@ -0,0 +1,243 @@
+use clap::ValueEnum;
+use image::{ImageDecoder, ImageError, ImageFormat, ImageReader};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// the libjpeg luminance quantization table that quality settings scale
+const STD_LUMINANCE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56, 14, 17, 22, 29, 51,
+    87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113, 92, 49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Rule {
+    /// More pixels first
+    Resolution,
+    /// Lossless formats (PNG, TIFF, BMP, lossless WebP, ...) first
+    Lossless,
+    /// Least compressed first, lossless images count as quality 100
+    Quality,
+    /// More bits per channel first
+    BitDepth,
+    /// Bigger files first
+    Size,
+}
+
+// what list & review rank by unless told otherwise
+pub const DEFAULT_RULES: [Rule; 5] = [
+    Rule::Resolution,
+    Rule::Lossless,
+    Rule::Quality,
+    Rule::BitDepth,
+    Rule::Size,
+];
+
+// properties of the file used to pick the best copy out of a group of duplicates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub width: u32,
+    pub height: u32,
+    // bits per channel
+    pub bit_depth: u8,
+    pub file_size: u64,
+    pub format: String,
+    pub lossless: bool,
+    // estimated from the quantization tables, jpegs only
+    pub jpeg_quality: Option<u8>,
+}
+
+impl Metadata {
+    // None if the compression is unknown, e.g. lossy webp
+    fn quality(&self) -> Option<u8> {
+        if self.lossless {
+            Some(100)
+        } else {
+            self.jpeg_quality
+        }
+    }
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} {} {}bit size={}",
+            self.width, self.height, self.format, self.bit_depth, self.file_size
+        )?;
+        if let Some(q) = self.jpeg_quality {
+            write!(f, " quality={}", q)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn read(path: &Path) -> Result<Metadata, ImageError> {
+    let bytes = std::fs::read(path)?;
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = reader.format();
+    let decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let color = decoder.original_color_type();
+    let lossless = match format {
+        Some(ImageFormat::Png | ImageFormat::Tiff | ImageFormat::Bmp | ImageFormat::Tga | ImageFormat::Qoi)
+        | Some(ImageFormat::Pnm | ImageFormat::Farbfeld) => true,
+        // lossless webps use the VP8L bitstream, lossy ones VP8
+        Some(ImageFormat::WebP) => bytes.windows(4).take(64).any(|w| w == b"VP8L"),
+        _ => false,
+    };
+    Ok(Metadata {
+        width,
+        height,
+        bit_depth: (color.bits_per_pixel() / color.channel_count().max(1) as u16) as u8,
+        file_size: bytes.len() as u64,
+        format: format.map(|f| format!("{:?}", f).to_lowercase()).unwrap_or_default(),
+        lossless,
+        jpeg_quality: match format {
+            Some(ImageFormat::Jpeg) => jpeg_quality(&bytes),
+            _ => None,
+        },
+    })
+}
+
+// estimates the libjpeg quality setting (1-100) a jpeg was saved w/ by comparing its luminance
+// quantization table to the standard one
+pub fn jpeg_quality(bytes: &[u8]) -> Option<u8> {
+    let table = luminance_table(bytes)?;
+    if table.iter().all(|q| *q == 1) {
+        return Some(100);
+    }
+    let sum: u32 = table.iter().map(|q| *q as u32).sum();
+    let std_sum: u32 = STD_LUMINANCE.iter().map(|q| *q as u32).sum();
+    // libjpeg scales the table by 5000 / quality below 50 & by 200 - 2 * quality above
+    let scale = sum as f64 * 100.0 / std_sum as f64;
+    let quality = if scale <= 100.0 {
+        (200.0 - scale) / 2.0
+    } else {
+        5000.0 / scale
+    };
+    Some(quality.round().clamp(1.0, 100.0) as u8)
+}
+
+// the 64 values of quantization table 0, in zigzag order
+fn luminance_table(bytes: &[u8]) -> Option<Vec<u16>> {
+    if bytes.get(..2)? != [0xff, 0xd8] {
+        return None;
+    }
+    let mut i = 2;
+    loop {
+        let marker = *bytes.get(i + 1)?;
+        // tables always come before the start of scan
+        if bytes[i] != 0xff || marker == 0xda || marker == 0xd9 {
+            return None;
+        }
+        let len = u16::from_be_bytes([*bytes.get(i + 2)?, *bytes.get(i + 3)?]) as usize;
+        let segment = bytes.get(i + 4..i + 2 + len)?;
+        if marker == 0xdb {
+            // a DQT segment can hold several tables, each w/ a precision & id byte
+            let mut j = 0;
+            while j < segment.len() {
+                let (precision, id) = (segment[j] >> 4, segment[j] & 0xf);
+                let size = if precision == 0 { 64 } else { 128 };
+                let values = segment.get(j + 1..j + 1 + size)?;
+                if id == 0 {
+                    return Some(if precision == 0 {
+                        values.iter().map(|v| *v as u16).collect()
+                    } else {
+                        values.chunks(2).map(|v| u16::from_be_bytes([v[0], v[1]])).collect()
+                    });
+                }
+                j += 1 + size;
+            }
+        }
+        i += 2 + len;
+    }
+}
+
+// 1 based rank of each path, the best copy is ranked 1. images w/o metadata are ranked last &
+// ties keep their original order
+pub fn rank(paths: &[&Path], meta: &HashMap<PathBuf, Metadata>, rules: &[Rule]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..paths.len()).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (meta.get(paths[*a]), meta.get(paths[*b]));
+        rules
+            .iter()
+            .map(|rule| compare(b, a, *rule))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    let mut ranks = vec![0; paths.len()];
+    for (r, i) in order.into_iter().enumerate() {
+        ranks[i] = r + 1;
+    }
+    ranks
+}
+
+// Greater if a is the better copy according to the rule
+fn compare(a: Option<&Metadata>, b: Option<&Metadata>, rule: Rule) -> Ordering {
+    let (a, b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        (a, b) => return a.is_some().cmp(&b.is_some()),
+    };
+    match rule {
+        Rule::Resolution => (a.width as u64 * a.height as u64).cmp(&(b.width as u64 * b.height as u64)),
+        Rule::Lossless => a.lossless.cmp(&b.lossless),
+        Rule::Quality => a.quality().cmp(&b.quality()),
+        Rule::BitDepth => a.bit_depth.cmp(&b.bit_depth),
+        Rule::Size => a.file_size.cmp(&b.file_size),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::RgbImage;
+
+    fn jpeg(quality: u8) -> Vec<u8> {
+        let img = RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 128]));
+        let mut bytes = Vec::new();
+        JpegEncoder::new_with_quality(&mut bytes, quality)
+            .encode_image(&img)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn estimates_jpeg_quality() {
+        for quality in [30, 75, 92] {
+            let estimate = jpeg_quality(&jpeg(quality)).unwrap();
+            assert!(
+                estimate.abs_diff(quality) <= 2,
+                "quality={} estimate={}",
+                quality,
+                estimate
+            );
+        }
+        assert_eq!(jpeg_quality(b"\x89PNG"), None);
+    }
+
+    #[test]
+    fn ranks_by_rules_in_order() {
+        let m = |width, lossless, file_size| Metadata {
+            width,
+            height: width,
+            bit_depth: 8,
+            file_size,
+            format: String::new(),
+            lossless,
+            jpeg_quality: None,
+        };
+        let paths = [Path::new("/small.png"), Path::new("/big.jpg"), Path::new("/big.png")];
+        let meta: HashMap<PathBuf, Metadata> = [(100, true, 10), (200, false, 30), (200, true, 20)]
+            .into_iter()
+            .zip(paths)
+            .map(|((w, l, s), p)| (p.to_path_buf(), m(w, l, s)))
+            .collect();
+        assert_eq!(rank(&paths, &meta, &[Rule::Resolution, Rule::Lossless]), vec![3, 2, 1]);
+        assert_eq!(rank(&paths, &meta, &[Rule::Size]), vec![3, 1, 2]);
+        assert_eq!(rank(&paths, &HashMap::new(), &[Rule::Size]), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::group::Group;
+use crate::quality::{self, Metadata, Rule};
+use crate::trash::local_time;
+use image::{ImageReader, ImageResult};
+use log::error;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// max width & height of the thumbnails in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+.members { display: flex; flex-wrap: wrap; gap: 1em; }
+.member { border: 2px solid #ccc; border-radius: 6px; padding: 0.5em; width: 272px; }
+.member.keep { border-color: #2a7; }
+.member img { display: block; margin: 0 auto 0.5em; max-width: 256px; max-height: 256px; }
+.member dl { display: grid; grid-template-columns: auto 1fr; gap: 0.1em 0.5em; margin: 0; font-size: 0.85em; }
+.member dt { color: #777; }
+.member dd { margin: 0; overflow-wrap: anywhere; }
+.badge { background: #2a7; color: #fff; border-radius: 4px; padding: 0 0.4em; font-size: 0.85em; }
+table { border-collapse: collapse; }
+td, th { padding: 0.3em 0.8em; text-align: left; border-bottom: 1px solid #eee; }
+td img { max-width: 64px; max-height: 64px; }
+";
+
+// writes index.html w/ every group & a group-<id>.html page per group into out, thumbnails go in
+// out/thumbs. the member ranked best by the rules is recommended as the one to keep
+pub fn write(out: &Path, groups: &[Group], meta: &HashMap<PathBuf, Metadata>, rules: &[Rule]) -> io::Result<()> {
+    fs::create_dir_all(out.join("thumbs"))?;
+    let mut rows = String::new();
+    for group in groups {
+        let paths: Vec<&Path> = group.members.iter().map(|m| m.path.as_path()).collect();
+        let ranks = quality::rank(&paths, meta, rules);
+        let keeper = ranks.iter().position(|r| *r == 1).unwrap_or(0);
+        let thumbs: Vec<Option<String>> = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| thumbnail(out, path, &format!("{}-{}.png", group.id, i)))
+            .collect();
+
+        let mut members = String::new();
+        for (i, member) in group.members.iter().enumerate() {
+            let mut fields = vec![("rank", ranks[i].to_string())];
+            if let Some(m) = meta.get(&member.path) {
+                fields.push(("dimensions", format!("{}x{}", m.width, m.height)));
+                fields.push(("format", format!("{} {}bit", m.format, m.bit_depth)));
+                fields.push(("file size", m.file_size.to_string()));
+                if let Some(q) = m.jpeg_quality {
+                    fields.push(("quality", q.to_string()));
+                }
+            }
+            if let Ok(secs) = fs::metadata(&member.path)
+                .and_then(|f| f.modified())
+                .map(|t| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+            {
+                fields.push(("modified", local_time(secs as i64)));
+            }
+            match (&member.transformation, member.dist) {
+                (Some(t), _) => fields.push(("transformation", t.clone())),
+                // the others are compared to the first member
+                _ if i == 0 => {}
+                (None, Some(dist)) => fields.push(("distance", dist.to_string())),
+                (None, None) => fields.push(("distance", "linked through other members".to_string())),
+            }
+            let fields: String = fields
+                .iter()
+                .map(|(k, v)| format!("<dt>{}</dt><dd>{}</dd>", k, escape(v)))
+                .collect();
+            members += &format!(
+                "<div class=\"member{}\">{}<dl><dt>path</dt><dd>{}{}</dd>{}</dl></div>\n",
+                if i == keeper { " keep" } else { "" },
+                img(&thumbs[i]),
+                escape(&member.path.to_string_lossy()),
+                if i == keeper {
+                    " <span class=\"badge\">keep</span>"
+                } else {
+                    ""
+                },
+                fields
+            );
+        }
+        fs::write(
+            out.join(format!("group-{}.html", group.id)),
+            page(
+                &format!("Group {}", group.id),
+                &format!(
+                    "<p><a href=\"index.html\">all groups</a></p>\n<p>{} members matched by {}</p>\n<div class=\"members\">\n{}</div>",
+                    group.members.len(),
+                    escape(&group.kind),
+                    members
+                ),
+            ),
+        )?;
+        rows += &format!(
+            "<tr><td>{}</td><td><a href=\"group-{}.html\">group {}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            img(&thumbs[keeper]),
+            group.id,
+            group.id,
+            escape(&group.kind),
+            group.members.len(),
+            escape(&paths[keeper].to_string_lossy())
+        );
+    }
+    fs::write(
+        out.join("index.html"),
+        page(
+            "Duplicate images",
+            &format!(
+                "<p>{} groups</p>\n<table>\n<tr><th></th><th>group</th><th>kind</th><th>members</th><th>keep</th></tr>\n{}</table>",
+                groups.len(),
+                rows
+            ),
+        ),
+    )
+}
+
+// saves a thumbnail to out/thumbs/name & returns its path relative to out
+fn thumbnail(out: &Path, path: &Path, name: &str) -> Option<String> {
+    let save = || -> ImageResult<()> {
+        let img = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+        // small images are kept as is, only big ones are scaled down
+        let img = if img.width() > THUMBNAIL_SIZE || img.height() > THUMBNAIL_SIZE {
+            img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        } else {
+            img
+        };
+        img.save(out.join("thumbs").join(name))
+    };
+    let result = save();
+    match result {
+        Ok(_) => Some(format!("thumbs/{}", name)),
+        Err(err) => {
+            error!("Failed to make a thumbnail of {:?}: {}", path, err);
+            None
+        }
+    }
+}
+
+fn img(thumb: &Option<String>) -> String {
+    match thumb {
+        Some(src) => format!("<img src=\"{}\" alt=\"\">", escape(src)),
+        None => "<p>no thumbnail</p>".to_string(),
+    }
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n{}\n</body>\n</html>\n",
+        title, STYLE, title, body
+    )
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&#39;",
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::Member;
+    use image::{GenericImageView, RgbImage};
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape("<a href=\"x\">it's & that</a>"),
+            "&lt;a href=&quot;x&quot;&gt;it&#39;s &amp; that&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn writes_pages_and_thumbnails() {
+        let dir = tempfile::tempdir().unwrap();
+        let (small, big, out) = (
+            dir.path().join("small.png"),
+            dir.path().join("big.png"),
+            dir.path().join("out"),
+        );
+        RgbImage::new(10, 10).save(&small).unwrap();
+        RgbImage::new(600, 300).save(&big).unwrap();
+        let member = |path: &Path, dist| Member {
+            path: path.to_path_buf(),
+            transformation: None,
+            dist: Some(dist),
+        };
+        let group = Group {
+            id: 1,
+            kind: "phash".to_string(),
+            members: vec![member(&small, 0), member(&big, 3)],
+        };
+        let meta = |width, height| Metadata {
+            width,
+            height,
+            bit_depth: 8,
+            file_size: 100,
+            format: "png".to_string(),
+            lossless: true,
+            jpeg_quality: None,
+        };
+        let meta = HashMap::from([(small.clone(), meta(10, 10)), (big.clone(), meta(600, 300))]);
+        write(&out, &[group], &meta, &[Rule::Resolution]).unwrap();
+
+        // the bigger image ranks best, so it's the one to keep
+        let index = fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<img src=\"thumbs/1-1.png\""));
+        assert!(index.contains(&escape(&big.to_string_lossy())));
+        let page = fs::read_to_string(out.join("group-1.html")).unwrap();
+        let (first, second) = page.split_once("<div class=\"member keep\">").unwrap();
+        assert!(first.contains(&escape(&small.to_string_lossy())));
+        assert!(!first.contains("<dt>distance</dt>"));
+        assert!(second.contains("<span class=\"badge\">keep</span>"));
+        assert!(second.contains("<dt>distance</dt><dd>3</dd>"));
+        assert!(second.contains("<dt>dimensions</dt><dd>600x300</dd>"));
+
+        let thumb = |name| image::open(out.join("thumbs").join(name)).unwrap().dimensions();
+        assert_eq!(thumb("1-0.png"), (10, 10));
+        assert_eq!(thumb("1-1.png"), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::group::Group;
+use crate::quality::Metadata;
+use crate::trash::local_time;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use term::{Key, Terminal};
+
+pub mod preview;
+pub mod term;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Undecided,
+    Keep,
+    Delete,
+    NotDuplicate,
+}
+
+impl Decision {
+    fn mark(&self) -> &'static str {
+        match self {
+            Decision::Undecided => "[ ]",
+            Decision::Keep => "[K]",
+            Decision::Delete => "[D]",
+            Decision::NotDuplicate => "[N]",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Continue,
+    Apply,
+    Quit,
+}
+
+// which group & member is selected & what was decided for every member so far
+pub struct Review<'a> {
+    groups: &'a [Group],
+    pub decisions: Vec<Vec<Decision>>,
+    group: usize,
+    row: usize,
+    message: String,
+}
+
+impl<'a> Review<'a> {
+    // keepers are marked as kept from the start, everything else is undecided
+    pub fn new(groups: &'a [Group], keepers: &[usize]) -> Review<'a> {
+        let decisions = groups
+            .iter()
+            .zip(keepers)
+            .map(|(g, k)| {
+                (0..g.members.len())
+                    .map(|i| if i == *k { Decision::Keep } else { Decision::Undecided })
+                    .collect()
+            })
+            .collect();
+        Review {
+            groups,
+            decisions,
+            group: 0,
+            row: 0,
+            message: String::new(),
+        }
+    }
+
+    pub fn handle(&mut self, key: Key) -> Outcome {
+        self.message.clear();
+        let len = self.groups[self.group].members.len();
+        match key {
+            Key::Up | Key::Char('k') => self.row = self.row.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.row = (self.row + 1).min(len - 1),
+            Key::Right | Key::Enter | Key::Char('l') => self.select_group(self.group + 1),
+            Key::Left | Key::Char('h') => self.select_group(self.group.saturating_sub(1)),
+            Key::Char('s') => self.decide(Decision::Keep),
+            Key::Char('d') => self.decide(Decision::Delete),
+            Key::Char('n') => self.decide(Decision::NotDuplicate),
+            Key::Char('u') => self.decide(Decision::Undecided),
+            // members marked as not duplicates aren't copies, so one of the others has to stay
+            Key::Char('a') => match self.decisions.iter().position(|d| {
+                d.contains(&Decision::Delete) && !d.iter().any(|d| matches!(d, Decision::Keep | Decision::Undecided))
+            }) {
+                Some(i) => {
+                    self.select_group(i);
+                    self.message = "every copy in this group is marked for deletion, keep one".to_string();
+                }
+                None => return Outcome::Apply,
+            },
+            Key::Char('q') => return Outcome::Quit,
+            _ => {}
+        }
+        Outcome::Continue
+    }
+
+    fn select_group(&mut self, group: usize) {
+        if group < self.groups.len() && group != self.group {
+            self.group = group;
+            self.row = 0;
+        }
+    }
+
+    // moves on to the next member so a group can be decided w/ one key per file
+    fn decide(&mut self, decision: Decision) {
+        self.decisions[self.group][self.row] = decision;
+        self.row = (self.row + 1).min(self.groups[self.group].members.len() - 1);
+    }
+
+    fn draw(&self, meta: &HashMap<PathBuf, Metadata>, cols: u16) -> String {
+        let group = &self.groups[self.group];
+        let mut lines = vec![
+            format!(
+                "group {}/{} kind={} members={}",
+                self.group + 1,
+                self.groups.len(),
+                group.kind,
+                group.members.len()
+            ),
+            "s keep  d delete  n not a duplicate  u undecided  ←/→ group  a apply  q quit".to_string(),
+            String::new(),
+        ];
+        for (i, member) in group.members.iter().enumerate() {
+            let mut line = format!("{} {}", self.decisions[self.group][i].mark(), member.path.display());
+            if let Some(m) = meta.get(&member.path) {
+                line += &format!("  {}x{} {}", m.width, m.height, m.format);
+            }
+            if let Ok(file) = fs::metadata(&member.path) {
+                line += &format!("  size={}", file.len());
+                if let Ok(secs) = file
+                    .modified()
+                    .map(|t| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+                {
+                    line += &format!("  mtime={}", local_time(secs as i64));
+                }
+            } else {
+                line += "  (missing)";
+            }
+            match (&member.transformation, member.dist) {
+                (Some(t), _) => line += &format!("  transformation={}", t),
+                (None, Some(dist)) if i > 0 => line += &format!("  dist={}", dist),
+                _ => {}
+            }
+            let line: String = line.chars().take(cols as usize).collect();
+            lines.push(if i == self.row {
+                format!("\x1b[7m{}\x1b[0m", line)
+            } else {
+                line
+            });
+        }
+        let count = |d: Decision| self.decisions.iter().flatten().filter(|x| **x == d).count();
+        lines.push(String::new());
+        lines.push(format!(
+            "{} to delete, {} not duplicates  {}",
+            count(Decision::Delete),
+            count(Decision::NotDuplicate),
+            self.message
+        ));
+        lines.join("\r\n") + "\r\n\r\n"
+    }
+}
+
+// walks the groups until the user applies (returns the decisions) or quits (returns None)
+pub fn run(
+    groups: &[Group],
+    keepers: &[usize],
+    meta: &HashMap<PathBuf, Metadata>,
+    protocol: preview::Protocol,
+) -> io::Result<Option<Vec<Vec<Decision>>>> {
+    let mut terminal = Terminal::new()?;
+    let mut review = Review::new(groups, keepers);
+    let protocol = protocol.detect();
+    loop {
+        let (cols, rows, px_w, px_h) = terminal.size();
+        let mut screen = format!("{}\x1b[2J\x1b[H", preview::clear(protocol));
+        screen += &review.draw(meta, cols);
+        let used = review.groups[review.group].members.len() as u16 + 7;
+        if protocol != preview::Protocol::None && rows > used + 2 {
+            let path = &review.groups[review.group].members[review.row].path;
+            let cell = (px_w / cols.max(1), px_h / rows.max(1));
+            match preview::render(path, protocol, cols / 2, rows - used - 1, cell) {
+                Ok(img) => screen += &img,
+                Err(err) => screen += &format!("no preview: {}", err),
+            }
+        }
+        print!("{}", screen);
+        io::stdout().flush()?;
+        match review.handle(terminal.read_key()?) {
+            Outcome::Continue => {}
+            Outcome::Apply => {
+                print!("{}", preview::clear(protocol));
+                return Ok(Some(review.decisions));
+            }
+            Outcome::Quit => {
+                print!("{}", preview::clear(protocol));
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::Member;
+
+    fn group(n: usize) -> Group {
+        Group {
+            id: 1,
+            kind: "phash".to_string(),
+            members: (0..n)
+                .map(|i| Member {
+                    path: PathBuf::from(format!("/{}.png", i)),
+                    transformation: None,
+                    dist: Some(0),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn decisions_advance_the_selection() {
+        let groups = [group(3)];
+        let mut review = Review::new(&groups, &[0]);
+        review.handle(Key::Down);
+        review.handle(Key::Char('d'));
+        review.handle(Key::Char('n'));
+        assert_eq!(
+            review.decisions[0],
+            vec![Decision::Keep, Decision::Delete, Decision::NotDuplicate]
+        );
+    }
+
+    #[test]
+    fn wont_apply_when_a_whole_group_is_deleted() {
+        let groups = [group(2), group(2)];
+        let mut review = Review::new(&groups, &[0, 0]);
+        review.handle(Key::Down);
+        review.handle(Key::Char('d'));
+        assert_eq!(review.handle(Key::Char('a')), Outcome::Apply);
+        review.handle(Key::Right);
+        review.handle(Key::Char('d'));
+        review.handle(Key::Char('d'));
+        assert_eq!(review.handle(Key::Char('a')), Outcome::Continue);
+        assert_eq!(review.group, 1);
+        review.handle(Key::Char('s'));
+        assert_eq!(review.handle(Key::Char('a')), Outcome::Apply);
+    }
+
+    #[test]
+    fn not_duplicates_dont_count_as_kept() {
+        let groups = [group(3)];
+        let mut review = Review::new(&groups, &[0]);
+        review.handle(Key::Char('n'));
+        review.handle(Key::Char('d'));
+        review.handle(Key::Char('d'));
+        assert_eq!(review.handle(Key::Char('a')), Outcome::Continue);
+        review.handle(Key::Up);
+        review.handle(Key::Char('u'));
+        assert_eq!(review.handle(Key::Char('a')), Outcome::Apply);
+    }
+}
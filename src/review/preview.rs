@@ -0,0 +1,206 @@
+use clap::ValueEnum;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageFormat, ImageReader, RgbImage};
+use std::io::Cursor;
+use std::path::Path;
+
+// kitty wants the base64 payload split into chunks of at most this many bytes
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Protocol {
+    /// Kitty graphics if the terminal looks like it supports it, colored text otherwise
+    Auto,
+    /// Kitty graphics protocol (kitty, WezTerm, Ghostty, ...)
+    Kitty,
+    /// Sixel graphics (xterm -ti vt340, foot, mlterm, ...)
+    Sixel,
+    /// Colored half block characters, works in any terminal w/ true color
+    Text,
+    /// No previews
+    None,
+}
+
+impl Protocol {
+    // there's no reliable way to detect sixel support w/o querying the terminal, so it's opt in
+    pub fn detect(self) -> Protocol {
+        if self != Protocol::Auto {
+            return self;
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || program == "WezTerm"
+        {
+            Protocol::Kitty
+        } else {
+            Protocol::Text
+        }
+    }
+}
+
+// escape sequences drawing the image at the cursor, fit into cols x rows cells. cell_px is the size of
+// a cell in pixels if the terminal reports it
+pub fn render(
+    path: &Path,
+    protocol: Protocol,
+    cols: u16,
+    rows: u16,
+    cell_px: (u16, u16),
+) -> Result<String, ImageError> {
+    let img = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+    let (cell_w, cell_h) = match cell_px {
+        (0, _) | (_, 0) => (8, 16),
+        (w, h) => (w as u32, h as u32),
+    };
+    Ok(match protocol {
+        Protocol::Kitty => kitty(&img.resize(cols as u32 * cell_w, rows as u32 * cell_h, FilterType::Triangle))?,
+        Protocol::Sixel => sixel(
+            &img.resize(cols as u32 * cell_w, rows as u32 * cell_h, FilterType::Triangle)
+                .into_rgb8(),
+        ),
+        Protocol::Text => text(
+            &img.resize(cols as u32, rows as u32 * 2, FilterType::Triangle)
+                .into_rgb8(),
+        ),
+        Protocol::Auto | Protocol::None => String::new(),
+    })
+}
+
+// removes every image drawn w/ the kitty protocol, other protocols are cleared w/ the screen
+pub fn clear(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::Kitty => "\x1b_Ga=d\x1b\\",
+        _ => "",
+    }
+}
+
+fn kitty(img: &DynamicImage) -> Result<String, ImageError> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    let payload = base64(&png);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        let control = if i == 0 {
+            format!("a=T,f=100,C=1,m={}", more)
+        } else {
+            format!("m={}", more)
+        };
+        out += &format!("\x1b_G{};{}\x1b\\", control, String::from_utf8_lossy(chunk));
+    }
+    Ok(out)
+}
+
+// quantized to a 6x6x6 color cube, which is plenty for a preview
+fn sixel(img: &RgbImage) -> String {
+    let level = |c: u8| (c as u16 * 5 + 127) / 255;
+    let index = |p: &image::Rgb<u8>| (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as usize;
+    let mut out = format!("\x1bPq\"1;1;{};{}", img.width(), img.height());
+    for i in 0..216 {
+        let pct = |l: usize| l * 100 / 5;
+        out += &format!("#{};2;{};{};{}", i, pct(i / 36), pct(i / 6 % 6), pct(i % 6));
+    }
+    for band in (0..img.height()).step_by(6) {
+        // bits of each column for every color used in this band of 6 rows
+        let mut colors: Vec<Option<Vec<u8>>> = vec![None; 216];
+        for dy in 0..6.min(img.height() - band) {
+            for x in 0..img.width() {
+                let c = index(img.get_pixel(x, band + dy));
+                colors[c].get_or_insert_with(|| vec![0; img.width() as usize])[x as usize] |= 1 << dy;
+            }
+        }
+        for (c, bits) in colors.iter().enumerate() {
+            let Some(bits) = bits else { continue };
+            out += &format!("#{}", c);
+            // run length encoded as !<count><char>
+            let mut x = 0;
+            while x < bits.len() {
+                let run = bits[x..].iter().take_while(|b| **b == bits[x]).count();
+                let ch = (0x3f + bits[x]) as char;
+                if run > 3 {
+                    out += &format!("!{}{}", run, ch);
+                } else {
+                    out.extend(std::iter::repeat_n(ch, run));
+                }
+                x += run;
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out + "\x1b\\"
+}
+
+// every cell shows 2 pixels, the top one as the foreground of ▀ & the bottom one as the background
+fn text(img: &RgbImage) -> String {
+    let mut out = String::new();
+    for y in (0..img.height()).step_by(2) {
+        for x in 0..img.width() {
+            let top = img.get_pixel(x, y);
+            let bottom = if y + 1 < img.height() {
+                img.get_pixel(x, y + 1)
+            } else {
+                top
+            };
+            out += &format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+                top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+            );
+        }
+        out += "\x1b[0m\r\n";
+    }
+    out
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn text_uses_one_line_per_2_rows() {
+        let img = RgbImage::new(4, 5);
+        assert_eq!(text(&img).matches("\r\n").count(), 3);
+    }
+
+    #[test]
+    fn sixel_has_one_band_per_6_rows() {
+        let img = RgbImage::from_pixel(10, 7, image::Rgb([255, 0, 0]));
+        let out = sixel(&img);
+        assert!(out.starts_with("\x1bPq\"1;1;10;7"));
+        assert!(out.ends_with("-\x1b\\"));
+        assert_eq!(out.matches('-').count(), 2);
+        // pure red is color 5 * 36, a full band of it is 10 columns w/ all 6 bits set
+        assert!(out.contains("#180!10~$"));
+    }
+}
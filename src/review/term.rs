@@ -0,0 +1,135 @@
+use std::io::{self, Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Char(char),
+    Other,
+}
+
+// puts the terminal in raw mode on the alternate screen until dropped
+pub struct Terminal {
+    #[cfg(unix)]
+    original: libc::termios,
+    // bytes read but not parsed yet, several keys can arrive in one read
+    pending: Vec<u8>,
+}
+
+impl Terminal {
+    #[cfg(unix)]
+    pub fn new() -> io::Result<Terminal> {
+        // SAFETY: isatty only looks at the fd
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+            return Err(io::Error::other("stdin is not a terminal"));
+        }
+        // SAFETY: tcgetattr fills in the struct, which is only used after it succeeds
+        let original = unsafe {
+            let mut t: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut t) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            t
+        };
+        let mut raw = original;
+        // SAFETY: raw is a valid termios copied from the terminal's own settings
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        // alternate screen & hidden cursor
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(Terminal {
+            original,
+            pending: Vec::new(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> io::Result<Terminal> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "review needs a unix terminal",
+        ))
+    }
+
+    // (columns, rows, pixel width, pixel height), the pixel sizes are 0 if the terminal doesn't say
+    #[cfg(unix)]
+    pub fn size(&self) -> (u16, u16, u16, u16) {
+        // SAFETY: TIOCGWINSZ only writes to the winsize passed in
+        let ws = unsafe {
+            let mut ws: libc::winsize = std::mem::zeroed();
+            libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws);
+            ws
+        };
+        match (ws.ws_col, ws.ws_row) {
+            (0, _) | (_, 0) => (80, 24, 0, 0),
+            (cols, rows) => (cols, rows, ws.ws_xpixel, ws.ws_ypixel),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn size(&self) -> (u16, u16, u16, u16) {
+        (80, 24, 0, 0)
+    }
+
+    pub fn read_key(&mut self) -> io::Result<Key> {
+        if self.pending.is_empty() {
+            let mut buf = [0u8; 64];
+            let n = io::stdin().read(&mut buf)?;
+            self.pending.extend_from_slice(&buf[..n]);
+        }
+        let (key, len) = parse_key(&self.pending);
+        self.pending.drain(..len.min(self.pending.len()));
+        Ok(key)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        // SAFETY: restores the settings read in new
+        #[cfg(unix)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
+
+// the first key & how many bytes it took
+fn parse_key(bytes: &[u8]) -> (Key, usize) {
+    match bytes {
+        [0x1b, b'[' | b'O', b'A', ..] => (Key::Up, 3),
+        [0x1b, b'[' | b'O', b'B', ..] => (Key::Down, 3),
+        [0x1b, b'[' | b'O', b'C', ..] => (Key::Right, 3),
+        [0x1b, b'[' | b'O', b'D', ..] => (Key::Left, 3),
+        // other escape sequences are skipped whole
+        [0x1b, ..] => (Key::Other, bytes.len()),
+        [b'\r' | b'\n', ..] => (Key::Enter, 1),
+        // ctrl-c, raw mode doesn't turn it into a signal
+        [0x03, ..] => (Key::Char('q'), 1),
+        [b, ..] if b.is_ascii_graphic() || *b == b' ' => (Key::Char(*b as char), 1),
+        _ => (Key::Other, 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(parse_key(b"\x1b[A"), (Key::Up, 3));
+        assert_eq!(parse_key(b"\x1bODjd"), (Key::Left, 3));
+        assert_eq!(parse_key(b"jda"), (Key::Char('j'), 1));
+        assert_eq!(parse_key(b"\r"), (Key::Enter, 1));
+        assert_eq!(parse_key(b"\x1b[5~"), (Key::Other, 4));
+    }
+}
@@ -1,11 +1,27 @@
 use crate::db;
 use crate::hash;
+use crate::quality;
+use image::{ImageError, ImageReader, Rgb};
 use infer::{get_from_path, MatcherType};
 use log::{debug, error, info, warn};
 use std::fs::read_dir;
 use std::path::{Path, PathBuf};
 
-pub fn process_path(path: PathBuf, recursive: bool) {
+// transparent images are flattened onto background before perceptual hashing
+pub fn process_path(path: PathBuf, recursive: bool, background: Rgb<u8>) {
+    for curr in images(&path, recursive) {
+        let file_name = curr.to_str().unwrap_or("cannot print path due to non-UTF8 chars");
+        // one unreadable file shouldn't stop the whole scan
+        if let Err(e) = hash_img(&curr, file_name, background) {
+            error!("Skipping file={} that can't be hashed: {}", file_name, e);
+        }
+    }
+}
+
+// the absolute paths of the image files at path, or in it if it's a dir
+#[allow(clippy::manual_while_let_some)]
+pub fn images(path: &Path, recursive: bool) -> Vec<PathBuf> {
+    let mut images = Vec::new();
     let mut stack: Vec<PathBuf> = Vec::new();
     // SAFETY: all paths passed to db::save need to be absolute
     stack.push(path.canonicalize().unwrap());
@@ -21,26 +37,66 @@ pub fn process_path(path: PathBuf, recursive: bool) {
                     }
                 }
             }
+        } else if is_img(&curr).unwrap_or(false) {
+            images.push(curr);
         } else {
-            let file_name = curr.to_str().unwrap_or("cannot print path due to non-UTF8 chars");
-            // TODO mv this to a seperate fn
-            if is_img(&curr).unwrap_or(false) {
-                let shs = hash::sha256::all_hashes_of_img_data(&curr).unwrap();
-                let ph = hash::phash::hash_path(&curr).unwrap();
-                info!("file={} sha256s={:?} phash={:?}", file_name, shs, ph);
-                for sh in shs {
-                    if let Err(e) = db::save(&sh) {
-                        error!("Failed to save sha256 hash for {}: {}", file_name, e);
-                    }
-                }
-                if let Err(e) = db::save(&ph) {
-                    error!("Failed to save phash for {}: {}", file_name, e);
-                }
-            } else {
-                debug!("skipping file={}", file_name);
-            }
+            debug!(
+                "skipping file={}",
+                curr.to_str().unwrap_or("cannot print path due to non-UTF8 chars")
+            );
         }
     }
+    images
+}
+
+// decodes the image once for the sha256s of its pixels, its alpha, phash, color signature & crop
+// regions, nothing is saved unless all of them could be computed
+pub fn hash_img(path: &Path, file_name: &str, background: Rgb<u8>) -> Result<(), ImageError> {
+    let img = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+    let mut shs = hash::sha256::all_hashes_of_img_data(path, &img);
+    shs.push(hash::sha256::hash_path(path)?);
+    let has_alpha = hash::has_alpha(&img);
+    let flat = hash::flatten(img, background);
+    let color = hash::color::from_img(path, &flat);
+    let regions = hash::crop::hash(&flat);
+    let ph = hash::phash::from_img(path, flat);
+    // a frame that can't be decoded only costs the frame rows, the still image hashes are kept
+    let frames = match hash::frames::hash_path(path, background) {
+        Ok(frames) => Some(frames),
+        Err(e) => {
+            error!("Failed to hash the frames of {}: {}", file_name, e);
+            None
+        }
+    };
+    let meta = quality::read(path)?;
+    info!("file={} sha256s={:?} phash={:?} color={:?}", file_name, shs, ph, color);
+
+    for sh in shs {
+        if let Err(e) = db::save(&sh) {
+            error!("Failed to save sha256 hash for {}: {}", file_name, e);
+        }
+    }
+    if let Err(e) = db::save(&ph) {
+        error!("Failed to save phash for {}: {}", file_name, e);
+    }
+    if let Err(e) = db::save(&color) {
+        error!("Failed to save color signature for {}: {}", file_name, e);
+    }
+    if let Err(e) = db::save_crop_regions(path, &regions) {
+        error!("Failed to save crop regions for {}: {}", file_name, e);
+    }
+    if let Some(frames) = frames {
+        if let Err(e) = db::save_frames(path, &frames) {
+            error!("Failed to save frames for {}: {}", file_name, e);
+        }
+    }
+    if let Err(e) = db::save_alpha(path, has_alpha) {
+        error!("Failed to save alpha for {}: {}", file_name, e);
+    }
+    if let Err(e) = db::save_metadata(path, &meta) {
+        error!("Failed to save metadata for {}: {}", file_name, e);
+    }
+    Ok(())
 }
 
 fn is_img(path: &Path) -> Option<bool> {
@@ -0,0 +1,116 @@
+use clap::ValueEnum;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Shell {
+    Bash,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Action {
+    /// Delete redundant files
+    Rm,
+    /// Move redundant files into $QUARANTINE, keeping their full path under it
+    Mv,
+    /// Replace redundant files w/ symlinks to the keeper
+    Ln,
+}
+
+// every command is commented out, so running the script unedited does nothing
+pub fn header(action: Action) -> String {
+    let mut script = String::from(
+        "#!/usr/bin/env bash\n\
+         # generated by idup, uncomment the lines to run after reviewing them\n\
+         set -euo pipefail\n",
+    );
+    if action == Action::Mv {
+        script += "QUARANTINE=\"${QUARANTINE:-./idup-quarantine}\"\n";
+    }
+    script
+}
+
+// a comment w/ the group & keeper, then a comment & a commented out command for every other member.
+// notes are appended to the comment of the member at the same index
+pub fn group(id: usize, kind: &str, paths: &[&Path], notes: &[String], keeper: usize, action: Action) -> String {
+    let mut script = format!("\n# group {} kind={} members={}\n", id, kind, paths.len());
+    script += &format!("# keep {}{}\n", quote(paths[keeper]), notes[keeper]);
+    for (i, path) in paths.iter().enumerate().filter(|(i, _)| *i != keeper) {
+        let command = match action {
+            Action::Rm => format!("rm -- {}", quote(path)),
+            Action::Mv => {
+                // the full path is kept under $QUARANTINE, so files w/ the same name don't collide
+                let relative: PathBuf = path
+                    .components()
+                    .filter(|c| matches!(c, Component::Normal(_)))
+                    .collect();
+                format!(
+                    "mkdir -p -- \"$QUARANTINE\"/{} && mv -n -- {} \"$QUARANTINE\"/{}",
+                    quote(relative.parent().unwrap_or(Path::new(""))),
+                    quote(path),
+                    quote(&relative)
+                )
+            }
+            Action::Ln => format!("ln -sfn -- {} {}", quote(paths[keeper]), quote(path)),
+        };
+        if !notes[i].is_empty() {
+            script += &format!("#{}\n", notes[i]);
+        }
+        script += &format!("# {}\n", command);
+    }
+    script
+}
+
+// single quotes keep everything literal, paths w/ control chars (e.g. newlines, which would end the
+// comment) or invalid UTF-8 use $'...' w/ escapes instead
+pub fn quote(path: &Path) -> String {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    match std::str::from_utf8(bytes) {
+        Ok(s) if !s.chars().any(char::is_control) => format!("'{}'", s.replace('\'', "'\\''")),
+        _ => {
+            let mut quoted = String::from("$'");
+            for b in bytes {
+                match b {
+                    b'\\' | b'\'' => quoted += &format!("\\{}", *b as char),
+                    0x20..=0x7e => quoted.push(*b as char),
+                    _ => quoted += &format!("\\x{:02x}", b),
+                }
+            }
+            quoted + "'"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_paths() {
+        assert_eq!(quote(Path::new("/a b/it's.png")), r"'/a b/it'\''s.png'");
+        assert_eq!(quote(Path::new("/a\nb.png")), r"$'/a\x0ab.png'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quotes_invalid_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        assert_eq!(quote(Path::new(OsStr::from_bytes(b"/\xff'.png"))), r"$'/\xff\'.png'");
+    }
+
+    #[test]
+    fn quarantine_keeps_the_full_path() {
+        let paths = [Path::new("/a/x.png"), Path::new("/b/x.png")];
+        let script = group(1, "sha256", &paths, &[String::new(), String::new()], 0, Action::Mv);
+        assert!(script.contains("# mkdir -p -- \"$QUARANTINE\"/'b' && mv -n -- '/b/x.png' \"$QUARANTINE\"/'b/x.png'\n"));
+    }
+
+    #[test]
+    fn comments_out_every_command() {
+        let paths = [Path::new("/a.png"), Path::new("/b.png")];
+        let notes = [String::new(), " dist=3".to_string()];
+        let script = group(1, "phash", &paths, &notes, 0, Action::Ln);
+        assert!(script.lines().all(|l| l.is_empty() || l.starts_with('#')));
+        assert!(script.contains("# ln -sfn -- '/a.png' '/b.png'\n"));
+    }
+}
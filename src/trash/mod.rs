@@ -0,0 +1,271 @@
+#[cfg(unix)]
+use directories::BaseDirs;
+#[cfg(unix)]
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io;
+#[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+// moves a file into the freedesktop.org trash so it can be restored from a file manager, see
+// https://specifications.freedesktop.org/trash-spec/latest/. returns where the file ended up
+#[cfg(unix)]
+pub fn trash(path: &Path) -> io::Result<PathBuf> {
+    let home = BaseDirs::new()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "can't determine the home dir"))?
+        .data_dir()
+        .join("Trash");
+    create_private_dir(&home)?;
+    let dev = fs::symlink_metadata(path)?.dev();
+    if fs::metadata(&home)?.dev() == dev {
+        return trash_to(&home, path, None);
+    }
+    // files on other mounts go to the trash at the top of that mount, so they don't need copying
+    let topdir = topdir(path, dev)?;
+    // SAFETY: getuid can't fail
+    let uid = unsafe { libc::getuid() };
+    trash_to(&topdir_trash(&topdir, uid)?, path, Some(&topdir))
+}
+
+// the mount point the path lives on
+#[cfg(unix)]
+fn topdir(path: &Path, dev: u64) -> io::Result<PathBuf> {
+    let mut topdir = path.parent().unwrap_or(path).to_path_buf();
+    while let Some(parent) = topdir.parent() {
+        if fs::metadata(parent)?.dev() != dev {
+            break;
+        }
+        topdir = parent.to_path_buf();
+    }
+    Ok(topdir)
+}
+
+// $topdir/.Trash/$uid if the admin set up a sticky .Trash dir, $topdir/.Trash-$uid otherwise
+#[cfg(unix)]
+fn topdir_trash(topdir: &Path, uid: u32) -> io::Result<PathBuf> {
+    let shared = topdir.join(".Trash");
+    if let Ok(meta) = fs::symlink_metadata(&shared) {
+        // the spec says to ignore it if it's a symlink or isn't sticky
+        if meta.is_dir() && meta.permissions().mode() & 0o1000 != 0 {
+            let dir = shared.join(uid.to_string());
+            if create_owned_dir(&dir, uid).is_ok() {
+                return Ok(dir);
+            }
+        }
+    }
+    let dir = topdir.join(format!(".Trash-{}", uid));
+    create_owned_dir(&dir, uid)?;
+    Ok(dir)
+}
+
+// like create_private_dir, but fails if the dir already existed as a symlink or belongs to another
+// user, so files can't be trashed into a dir someone else on a shared mount set up
+#[cfg(unix)]
+fn create_owned_dir(dir: &Path, uid: u32) -> io::Result<()> {
+    create_private_dir(dir)?;
+    let meta = fs::symlink_metadata(dir)?;
+    if !meta.is_dir() || meta.uid() != uid {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{:?} isn't a dir owned by uid {}", dir, uid),
+        ));
+    }
+    Ok(())
+}
+
+// moves path into trash_dir/files & writes its trash_dir/info/<name>.trashinfo. the path in the
+// info file is relative to topdir if given, absolute otherwise
+#[cfg(unix)]
+fn trash_to(trash_dir: &Path, path: &Path, topdir: Option<&Path>) -> io::Result<PathBuf> {
+    let (files, info) = (trash_dir.join("files"), trash_dir.join("info"));
+    create_private_dir(&files)?;
+    create_private_dir(&info)?;
+    let original = match topdir.and_then(|t| path.strip_prefix(t).ok()) {
+        Some(relative) => relative,
+        None => path,
+    };
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(original.as_os_str().as_encoded_bytes()),
+        now()
+    );
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    for i in 1.. {
+        let candidate = if i == 1 {
+            name.clone()
+        } else {
+            format!("{}.{}", name, i)
+        };
+        let info_path = info.join(format!("{}.trashinfo", candidate));
+        // creating the info file atomically is what reserves the name
+        let mut info_file = match OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(f) => f,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+        let dst = files.join(&candidate);
+        if dst.exists() {
+            let _ = fs::remove_file(&info_path);
+            continue;
+        }
+        let result = info_file
+            .write_all(contents.as_bytes())
+            .and_then(|_| fs::rename(path, &dst));
+        if let Err(err) = result {
+            let _ = fs::remove_file(&info_path);
+            return Err(err);
+        }
+        return Ok(dst);
+    }
+    unreachable!()
+}
+
+#[cfg(not(unix))]
+pub fn trash(_path: &Path) -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the trash is only supported on unix",
+    ))
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+// like a uri path, everything but unreserved chars & / is escaped
+#[cfg(unix)]
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for b in bytes {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(*b as char),
+            _ => encoded += &format!("%{:02X}", b),
+        }
+    }
+    encoded
+}
+
+#[cfg(unix)]
+fn now() -> String {
+    // SAFETY: passing null only returns the time
+    local_time(unsafe { libc::time(std::ptr::null_mut()) })
+}
+
+// seconds since the epoch as local YYYY-MM-DDThh:mm:ss
+#[cfg(unix)]
+pub fn local_time(secs: i64) -> String {
+    // SAFETY: localtime_r only writes to the struct passed in
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&secs, &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+// w/o localtime_r this is UTC, see https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+#[cfg(not(unix))]
+pub fn local_time(secs: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let z = days + 719468;
+    let (era, doe) = (z.div_euclid(146097), z.rem_euclid(146097));
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let (day, month) = (doy - (153 * mp + 2) / 5 + 1, if mp < 10 { mp + 3 } else { mp - 9 });
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn uid() -> u32 {
+        // SAFETY: getuid can't fail
+        unsafe { libc::getuid() }
+    }
+
+    #[test]
+    fn encodes_paths() {
+        assert_eq!(
+            percent_encode(b"/photos/10% off/a b.png"),
+            "/photos/10%25%20off/a%20b.png"
+        );
+    }
+
+    #[test]
+    fn trashes_w_info_and_unique_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let trash = dir.join("Trash");
+        for _ in 0..2 {
+            fs::write(dir.join("a b.png"), b"img").unwrap();
+            trash_to(&trash, &dir.join("a b.png"), None).unwrap();
+        }
+        assert!(trash.join("files/a b.png").exists());
+        assert!(trash.join("files/a b.png.2").exists());
+        let info = fs::read_to_string(trash.join("info/a b.png.2.trashinfo")).unwrap();
+        assert!(info.starts_with(&format!(
+            "[Trash Info]\nPath={}/a%20b.png\nDeletionDate=",
+            dir.display()
+        )));
+    }
+
+    #[test]
+    fn topdir_trash_is_relative() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("photos")).unwrap();
+        fs::write(dir.join("photos/a.png"), b"img").unwrap();
+        let uid = uid();
+        let trash = topdir_trash(dir, uid).unwrap();
+        assert_eq!(trash, dir.join(format!(".Trash-{}", uid)));
+        trash_to(&trash, &dir.join("photos/a.png"), Some(dir)).unwrap();
+        let info = fs::read_to_string(trash.join("info/a.png.trashinfo")).unwrap();
+        assert!(info.contains("\nPath=photos/a.png\n"));
+    }
+
+    #[test]
+    fn uses_sticky_shared_trash() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir(dir.join(".Trash")).unwrap();
+        fs::set_permissions(dir.join(".Trash"), fs::Permissions::from_mode(0o1777)).unwrap();
+        let uid = uid();
+        assert_eq!(topdir_trash(dir, uid).unwrap(), dir.join(format!(".Trash/{}", uid)));
+    }
+
+    #[test]
+    fn rejects_symlinked_or_foreign_trash() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let uid = uid();
+        fs::create_dir(dir.join("elsewhere")).unwrap();
+        std::os::unix::fs::symlink(dir.join("elsewhere"), dir.join(format!(".Trash-{}", uid))).unwrap();
+        assert!(topdir_trash(dir, uid).is_err());
+        // created by this user, so it isn't owned by the next uid
+        fs::create_dir(dir.join(format!(".Trash-{}", uid + 1))).unwrap();
+        assert!(topdir_trash(dir, uid + 1).is_err());
+    }
+}
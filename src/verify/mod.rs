@@ -0,0 +1,126 @@
+use crate::hash::flatten;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageError, ImageReader, Rgb};
+use std::path::Path;
+
+// both images are scaled to this size before comparing pixels
+const SIZE: u32 = 128;
+const WINDOW: u32 = 8;
+const STRIDE: u32 = 4;
+// stabilizing constants from the SSIM paper, (k * L)^2 w/ L = 255
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Score {
+    // structural similarity, 1.0 means identical
+    pub ssim: f64,
+    // peak signal to noise ratio in dB, infinite when identical
+    pub psnr: f64,
+}
+
+// transparent images are flattened onto background first, the same way they are for hashing
+pub fn compare_paths(a: &Path, b: &Path, background: Rgb<u8>) -> Result<Score, ImageError> {
+    let a = flatten(ImageReader::open(a)?.with_guessed_format()?.decode()?, background);
+    let b = flatten(ImageReader::open(b)?.with_guessed_format()?.decode()?, background);
+    Ok(compare(&a, &b))
+}
+
+pub fn compare(a: &DynamicImage, b: &DynamicImage) -> Score {
+    let a = a.resize_exact(SIZE, SIZE, FilterType::Triangle).into_luma8();
+    let b = b.resize_exact(SIZE, SIZE, FilterType::Triangle).into_luma8();
+    Score {
+        ssim: ssim(&a, &b),
+        psnr: psnr(&a, &b),
+    }
+}
+
+// mean SSIM over sliding windows, both images must have the same dimensions
+fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    let (w, h) = a.dimensions();
+    let n = (WINDOW * WINDOW) as f64;
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y in (0..=h - WINDOW).step_by(STRIDE as usize) {
+        for x in (0..=w - WINDOW).step_by(STRIDE as usize) {
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for dy in 0..WINDOW {
+                for dx in 0..WINDOW {
+                    let pa = a.get_pixel(x + dx, y + dy).0[0] as f64;
+                    let pb = b.get_pixel(x + dx, y + dy).0[0] as f64;
+                    sa += pa;
+                    sb += pb;
+                    saa += pa * pa;
+                    sbb += pb * pb;
+                    sab += pa * pb;
+                }
+            }
+            let (ma, mb) = (sa / n, sb / n);
+            let va = saa / n - ma * ma;
+            let vb = sbb / n - mb * mb;
+            let cov = sab / n - ma * mb;
+            total += ((2.0 * ma * mb + C1) * (2.0 * cov + C2)) / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+fn psnr(a: &GrayImage, b: &GrayImage) -> f64 {
+    let mut sum = 0.0;
+    for (pa, pb) in a.iter().zip(b.iter()) {
+        let d = *pa as f64 - *pb as f64;
+        sum += d * d;
+    }
+    let mse = sum / a.len() as f64;
+    if mse == 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgba, RgbaImage};
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(64, 64, |x, y| Luma([(x * 2 + y) as u8])))
+    }
+
+    #[test]
+    fn identical_images() {
+        let score = compare(&gradient(), &gradient());
+        assert!((score.ssim - 1.0).abs() < 1e-9);
+        assert!(score.psnr.is_infinite());
+    }
+
+    #[test]
+    fn inverted_image_is_dissimilar() {
+        let mut inverted = gradient();
+        inverted.invert();
+        let score = compare(&gradient(), &inverted);
+        assert!(score.ssim < 0.5);
+        assert!(score.psnr < 20.0);
+    }
+
+    #[test]
+    fn transparent_image_matches_its_flattened_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let (png, jpg) = (dir.path().join("a.png"), dir.path().join("a.jpg"));
+        // the left half is transparent but hides stripes in its rgb values
+        let img = RgbaImage::from_fn(64, 64, |x, y| {
+            if x < 32 {
+                let v = if y % 4 < 2 { 0 } else { 255 };
+                Rgba([v, v, v, 0])
+            } else {
+                let v = (x * 2 + y) as u8;
+                Rgba([v, v, v, 255])
+            }
+        });
+        img.save(&png).unwrap();
+        let white = Rgb([255, 255, 255]);
+        flatten(DynamicImage::ImageRgba8(img), white).save(&jpg).unwrap();
+        assert!(compare_paths(&png, &jpg, white).unwrap().ssim > 0.9);
+    }
+}